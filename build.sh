@@ -35,6 +35,8 @@ if [ "${PROFILE}" = release ]; then
 fi
 
 KERNEL_OUTPUT_DIR=target/${KERNEL_TARGET}/${PROFILE}
+USER_OUTPUT_DIR=target/${USER_TARGET}/${PROFILE}
+INITRD_DIR=target/initrd
 
 pstatus() {
     echo -e "[BUILD] \033[32;1m$@\033[0m"
@@ -69,10 +71,22 @@ build_test_program() {
     build_user_program "test_program"
 }
 
+build_initrd() {
+    pstatus "Creating initrd"
+    rm -rf ${INITRD_DIR}
+    mkdir -p ${INITRD_DIR}/bin ${INITRD_DIR}/dev
+
+    cp ${USER_OUTPUT_DIR}/test_program ${INITRD_DIR}/bin/test_program
+    cp ${USER_OUTPUT_DIR}/test_program ${INITRD_DIR}/init
+
+    tar -C ${INITRD_DIR} --format=ustar -cf ${KERNEL_OUTPUT_DIR}/initrd.tar .
+}
+
 build() {
     build_test_program
     build_kernel
     build_kernel_bin
+    build_initrd
 }
 
 case "$1" in
@@ -89,7 +103,8 @@ case "$1" in
     qemu)
         build
         shift
-        "${QEMU}" -kernel ${KERNEL_OUTPUT_DIR}/kernel.bin  ${QEMU_OPTS} $@
+        "${QEMU}" -kernel ${KERNEL_OUTPUT_DIR}/kernel.bin \
+            -initrd ${KERNEL_OUTPUT_DIR}/initrd.tar ${QEMU_OPTS} $@
         ;;
     *)
         ;;
@@ -1,5 +1,5 @@
 //! AArch64-specific task context implementation
-use core::{arch::global_asm, cell::UnsafeCell};
+use core::{arch::global_asm, cell::UnsafeCell, mem::size_of};
 
 use abi::error::Error;
 use alloc::boxed::Box;
@@ -9,6 +9,8 @@ use crate::mem::{
     ConvertAddress,
 };
 
+use super::exception::ExceptionFrame;
+
 struct StackBuilder {
     base: usize,
     sp: usize,
@@ -23,6 +25,8 @@ struct TaskContextInner {
 /// AArch64 implementation of a task context
 pub struct TaskContext {
     inner: UnsafeCell<TaskContextInner>,
+    stack_base: usize,
+    stack_size: usize,
 }
 
 const COMMON_CONTEXT_SIZE: usize = 8 * 14;
@@ -96,10 +100,10 @@ impl TaskContext {
 
         let sp = stack.build();
 
-        // TODO stack is leaked
-
         Ok(Self {
             inner: UnsafeCell::new(TaskContextInner { sp }),
+            stack_base,
+            stack_size: KERNEL_TASK_PAGES * 0x1000,
         })
     }
 
@@ -140,6 +144,41 @@ impl TaskContext {
 
         Ok(Self {
             inner: UnsafeCell::new(TaskContextInner { sp }),
+            stack_base,
+            stack_size: USER_TASK_PAGES * 0x1000,
+        })
+    }
+
+    /// Constructs a user thread context which resumes execution from a copy of the `frame` with
+    /// `x0` set to zero. The caller is responsible for setting up a valid address space for the
+    /// context.
+    pub fn user_fork(frame: &ExceptionFrame, ttbr0: usize) -> Result<Self, Error> {
+        const USER_TASK_PAGES: usize = 8;
+        let stack_base =
+            unsafe { phys::alloc_pages_contiguous(USER_TASK_PAGES, PageUsage::Used)?.virtualize() };
+
+        let mut stack = StackBuilder::new(stack_base, USER_TASK_PAGES * 0x1000);
+
+        let words = unsafe {
+            core::slice::from_raw_parts(
+                frame as *const _ as *const usize,
+                size_of::<ExceptionFrame>() / size_of::<usize>(),
+            )
+        };
+        // x0 is the first word of the frame
+        for &word in words[1..].iter().rev() {
+            stack.push(word);
+        }
+        stack.push(0);
+
+        stack.init_common(__aarch64_task_enter_forked as *const () as _, ttbr0);
+
+        let sp = stack.build();
+
+        Ok(Self {
+            inner: UnsafeCell::new(TaskContextInner { sp }),
+            stack_base,
+            stack_size: USER_TASK_PAGES * 0x1000,
         })
     }
 
@@ -162,11 +201,23 @@ impl TaskContext {
     }
 }
 
+impl Drop for TaskContext {
+    fn drop(&mut self) {
+        // The owner makes sure the task is no longer executing on this stack
+        for page in (self.stack_base..self.stack_base + self.stack_size).step_by(0x1000) {
+            unsafe {
+                phys::free_page(page.physicalize());
+            }
+        }
+    }
+}
+
 extern "C" {
     fn __aarch64_enter_task(to: *mut TaskContextInner) -> !;
     fn __aarch64_switch_task(to: *mut TaskContextInner, from: *mut TaskContextInner);
     fn __aarch64_task_enter_kernel();
     fn __aarch64_task_enter_user();
+    fn __aarch64_task_enter_forked();
 }
 
 global_asm!(include_str!("context.S"), context_size = const COMMON_CONTEXT_SIZE);
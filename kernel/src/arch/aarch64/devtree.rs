@@ -100,6 +100,27 @@ pub fn find_prop<'a>(node: &TNode<'a>, name: &str) -> Option<TProp<'a>> {
     node.props().find(|p| p.name().unwrap_or("") == name)
 }
 
+/// Returns the physical address range of the initial ramdisk passed by the loader, if any
+pub fn find_initrd(dt: &DeviceTree) -> Option<(usize, usize)> {
+    let chosen = dt.node_by_path("/chosen")?;
+    let start = find_prop(&chosen, "linux,initrd-start")?;
+    let end = find_prop(&chosen, "linux,initrd-end")?;
+
+    let start = prop_address(&start)?;
+    let end = prop_address(&end)?;
+
+    (start < end).then_some((start, end))
+}
+
+// Addresses in /chosen may be encoded as either one or two cells
+fn prop_address(prop: &TProp) -> Option<usize> {
+    match prop.raw().len() {
+        4 => prop.u32(0).ok().map(|v| v as usize),
+        8 => prop.u64(0).ok().map(|v| v as usize),
+        _ => None,
+    }
+}
+
 fn path_component_left(path: &str) -> (&str, &str) {
     if let Some((left, right)) = path.split_once('/') {
         (left, right.trim_start_matches('/'))
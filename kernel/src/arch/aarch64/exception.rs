@@ -2,12 +2,17 @@
 use core::{arch::global_asm, fmt};
 
 use aarch64_cpu::registers::{ELR_EL1, ESR_EL1, FAR_EL1, TTBR0_EL1, TTBR1_EL1, VBAR_EL1};
+use abi::error::Error;
 use tock_registers::interfaces::{Readable, Writeable};
 
 use crate::{
-    arch::{aarch64::cpu::Cpu, CpuMessage, PLATFORM},
+    arch::{
+        aarch64::{cpu::Cpu, usercopy},
+        CpuMessage, PLATFORM,
+    },
     debug::LogLevel,
     device::{interrupt::IrqContext, platform::Platform},
+    mem::KERNEL_VIRT_OFFSET,
     panic::panic_secondary,
     syscall::raw_syscall_handler,
     task::process::Process,
@@ -51,30 +56,30 @@ pub fn init_exceptions() {
     VBAR_EL1.set(vbar as u64);
 }
 
-fn dump_irrecoverable_exception(frame: &ExceptionFrame, ec: u64, iss: u64) {
+fn dump_irrecoverable_exception(level: LogLevel, frame: &ExceptionFrame, ec: u64, iss: u64) {
     let cpu = Cpu::get_local();
 
-    log_print_raw!(LogLevel::Fatal, "SYNC exception:\n");
-    log_print_raw!(LogLevel::Fatal, "FAR: {:#x}\n", FAR_EL1.get());
-    log_print_raw!(LogLevel::Fatal, "ELR: {:#x}\n", ELR_EL1.get());
-    log_print_raw!(LogLevel::Fatal, "ESR: {:#x}\n", ESR_EL1.get());
-    log_print_raw!(LogLevel::Fatal, "TTBR0_EL1: {:#x}\n", TTBR0_EL1.get());
-    log_print_raw!(LogLevel::Fatal, "TTBR1_EL1: {:#x}\n", TTBR1_EL1.get());
-    log_print_raw!(LogLevel::Fatal, "Register dump:\n");
-    log_print_raw!(LogLevel::Fatal, "{:?}\n", frame);
+    log_print_raw!(level, "SYNC exception:\n");
+    log_print_raw!(level, "FAR: {:#x}\n", FAR_EL1.get());
+    log_print_raw!(level, "ELR: {:#x}\n", ELR_EL1.get());
+    log_print_raw!(level, "ESR: {:#x}\n", ESR_EL1.get());
+    log_print_raw!(level, "TTBR0_EL1: {:#x}\n", TTBR0_EL1.get());
+    log_print_raw!(level, "TTBR1_EL1: {:#x}\n", TTBR1_EL1.get());
+    log_print_raw!(level, "Register dump:\n");
+    log_print_raw!(level, "{:?}\n", frame);
 
     if let Some(cpu) = cpu {
         let current = cpu.queue().current_process();
 
         if let Some(current) = current {
-            log_print_raw!(LogLevel::Fatal, "In process {}\n", current.id());
+            log_print_raw!(level, "In process {}\n", current.id());
         }
     }
 
     match ec {
         // Data abort from lower level
         0b100100 => {
-            log_print_raw!(LogLevel::Fatal, "Exception kind: Data Abort from EL0\n");
+            log_print_raw!(level, "Exception kind: Data Abort from EL0\n");
             let dfsc = iss & 0x3F;
 
             if iss & (1 << 24) != 0 {
@@ -88,7 +93,7 @@ fn dump_irrecoverable_exception(frame: &ExceptionFrame, ec: u64, iss: u64) {
                 let access_type_str = if iss & (1 << 6) != 0 { "write" } else { "read" };
 
                 log_print_raw!(
-                    LogLevel::Fatal,
+                    level,
                     "Invalid {} of a {} to/from {:#x}\n",
                     access_type_str,
                     access_size_str,
@@ -96,22 +101,43 @@ fn dump_irrecoverable_exception(frame: &ExceptionFrame, ec: u64, iss: u64) {
                 );
             }
 
-            log_print_raw!(LogLevel::Fatal, "DFSC = {:#x}\n", dfsc);
+            log_print_raw!(level, "DFSC = {:#x}\n", dfsc);
         }
         // Instruction abort from lower level
         0b100000 => {
-            log_print_raw!(
-                LogLevel::Fatal,
-                "Exception kind: Instruction Abort from EL0\n"
-            );
+            log_print_raw!(level, "Exception kind: Instruction Abort from EL0\n");
             let ifsc = iss & 0x3F;
-            log_print_raw!(LogLevel::Fatal, "IFSC = {:#x}\n", ifsc);
+            log_print_raw!(level, "IFSC = {:#x}\n", ifsc);
         }
 
         _ => (),
     }
 }
 
+fn handle_user_fault(ec: u64, iss: u64) -> Result<(), Error> {
+    let far = FAR_EL1.get() as usize;
+    let fsc = iss & 0x3F;
+    let is_write = ec != 0b100000 && iss & (1 << 6) != 0;
+
+    // Kernel faulting outside of the userspace memory is always fatal
+    if ec == 0b100101 && far >= KERNEL_VIRT_OFFSET {
+        return Err(Error::InvalidMemoryOperation);
+    }
+
+    let process = Process::get_current().ok_or(Error::InvalidMemoryOperation)?;
+    let space = process
+        .get_address_space()
+        .ok_or(Error::InvalidMemoryOperation)?;
+
+    match fsc & 0x3C {
+        // Translation fault, level 0..3
+        0b000100 => space.handle_fault(far),
+        // Permission fault, level 0..3
+        0b001100 if is_write => space.handle_cow_fault(far),
+        _ => Err(Error::InvalidMemoryOperation),
+    }
+}
+
 #[no_mangle]
 extern "C" fn __aa64_exc_sync_handler(frame: *mut ExceptionFrame) {
     let frame = unsafe { &mut *frame };
@@ -124,7 +150,7 @@ extern "C" fn __aa64_exc_sync_handler(frame: *mut ExceptionFrame) {
         0b010101 => {
             let func = frame.r[8];
             let args = &frame.r[0..6];
-            let result = raw_syscall_handler(func, args);
+            let result = raw_syscall_handler(func, args, frame);
             frame.r[0] = result;
         }
         // BRK in AArch64
@@ -132,9 +158,27 @@ extern "C" fn __aa64_exc_sync_handler(frame: *mut ExceptionFrame) {
             Process::current().exit(1);
             panic!("Cannot return here");
         }
+        // Data/Instruction Abort from EL0 or Data Abort from EL1 (when accessing user memory)
+        0b100100 | 0b100000 | 0b100101 if handle_user_fault(ec, esr_el1 & 0x1FFFFFF).is_ok() => {}
         _ => {
+            // Data Abort from EL1 while copying to/from user memory
+            if ec == 0b100101 {
+                if let Some(fixup) = usercopy::fault_fixup_address(frame.c[1] as usize) {
+                    frame.c[1] = fixup as u64;
+                    return;
+                }
+            }
+
             let iss = esr_el1 & 0x1FFFFFF;
-            dump_irrecoverable_exception(frame, ec, iss);
+
+            // Exception came from EL0, only the process has to die
+            if frame.c[0] & 0xF == 0 {
+                dump_irrecoverable_exception(LogLevel::Warning, frame, ec, iss);
+                Process::current().exit(1);
+                panic!("Cannot return here");
+            }
+
+            dump_irrecoverable_exception(LogLevel::Fatal, frame, ec, iss);
 
             panic!("Irrecoverable exception");
         }
@@ -15,10 +15,10 @@ use crate::{
     },
     debug,
     device::platform::Platform,
-    fs::devfs,
+    fs::{devfs, Initrd, INITRD_DATA},
     mem::{
         heap,
-        phys::{self, reserved::reserve_region, PageUsage, PhysicalMemoryRegion},
+        phys::{self, reserved::reserve_region, PhysicalMemoryRegion},
         ConvertAddress,
     },
     task,
@@ -43,6 +43,7 @@ pub mod gic;
 pub mod smp;
 pub mod table;
 pub mod timer;
+pub mod usercopy;
 
 pub(self) const BOOT_STACK_SIZE: usize = 65536;
 
@@ -143,6 +144,22 @@ impl AArch64 {
             },
         );
 
+        if let Some((start, end)) = devtree::find_initrd(dt) {
+            let aligned_start = start & !0xFFF;
+            let aligned_end = (end + 0xFFF) & !0xFFF;
+
+            reserve_region(
+                "initrd",
+                PhysicalMemoryRegion {
+                    base: aligned_start,
+                    size: aligned_end - aligned_start,
+                },
+            );
+
+            let data = core::slice::from_raw_parts(start.virtualize() as *const u8, end - start);
+            INITRD_DATA.init(Initrd { data });
+        }
+
         let regions = FdtMemoryRegionIter::new(dt);
         phys::init_from_iter(regions)
     }
@@ -173,11 +190,6 @@ pub fn kernel_main(dtb_phys: usize) -> ! {
             .init_physical_memory(dtb_phys)
             .expect("Failed to initialize the physical memory manager");
 
-        // Setup heap
-        let heap_base = phys::alloc_pages_contiguous(16, PageUsage::Used)
-            .expect("Could not allocate a block for heap");
-        heap::init_heap(heap_base.virtualize(), 16 * 0x1000);
-
         Cpu::init_local();
 
         devfs::init();
@@ -198,6 +210,8 @@ pub fn kernel_main(dtb_phys: usize) -> ! {
 
         task::init().expect("Failed to initialize the scheduler");
 
+        heap::dump_stats();
+
         // Initialize and enter the scheduler
         task::enter();
     }
@@ -8,18 +8,22 @@ use core::{
 use abi::error::Error;
 use bitflags::bitflags;
 
-use crate::mem::{
-    phys::{self, PageUsage},
-    table::{EntryLevel, NextPageTable, VirtualMemoryManager},
-    ConvertAddress, KERNEL_VIRT_OFFSET,
+use crate::{
+    mem::{
+        phys::{self, PageUsage},
+        table::{EntryLevel, NextPageTable, VirtualMemoryManager},
+        vma::{VirtualMemoryRegion, VirtualMemoryRegionList},
+        ConvertAddress, KERNEL_VIRT_OFFSET,
+    },
+    sync::IrqSafeSpinlock,
 };
 
-/// TODO
-#[derive(Clone)]
+/// Userspace address translation tables along with the regions reserved in them
 #[repr(C)]
 pub struct AddressSpace {
     l1: *mut PageTable<L1>,
     asid: u8,
+    regions: IrqSafeSpinlock<VirtualMemoryRegionList>,
 }
 
 /// Page table representing a single level of address translation
@@ -77,6 +81,10 @@ bitflags! {
         const AP_BOTH_READWRITE = 1 << 6;
         /// For page/block mappings, only allows read access for EL0/EL1
         const AP_BOTH_READONLY = 3 << 6;
+
+        /// (Software) For L3 mappings, indicates that the page is shared read-only and needs to be
+        /// copied on write
+        const COW = 1 << 55;
     }
 }
 
@@ -144,6 +152,11 @@ impl PageEntry<L3> {
             None
         }
     }
+
+    /// Returns the attributes of the page mapping
+    pub fn attributes(self) -> PageAttributes {
+        PageAttributes::from_bits_truncate(self.0)
+    }
 }
 
 impl<T: NonTerminalEntryLevel> PageEntry<T> {
@@ -315,38 +328,58 @@ impl VirtualMemoryManager for AddressSpace {
         len: usize,
         attrs: PageAttributes,
     ) -> Result<usize, Error> {
-        if hint.is_some() {
-            todo!();
-        }
-
         const TRY_ALLOC_START: usize = 0x100000000;
         const TRY_ALLOC_END: usize = 0xF00000000;
 
-        'l0: for base in (TRY_ALLOC_START..TRY_ALLOC_END - len * 0x1000).step_by(0x1000) {
-            for i in 0..len {
-                if self.translate(base + i * 0x1000).is_some() {
-                    continue 'l0;
-                }
-            }
+        let size = len.checked_mul(0x1000).ok_or(Error::InvalidArgument)?;
+        let mut regions = self.regions.lock();
 
-            for i in 0..len {
-                let page = phys::alloc_page(PageUsage::Used)?;
-                self.map_page(base + i * 0x1000, page, attrs)?;
+        let base = if let Some(base) = hint {
+            if base.checked_add(size).is_none() {
+                return Err(Error::InvalidArgument);
             }
+            if base & 0xFFF != 0 || !self.is_range_free(&regions, base, len) {
+                return Err(Error::AlreadyExists);
+            }
+            base
+        } else {
+            let end = TRY_ALLOC_END.checked_sub(size).ok_or(Error::OutOfMemory)?;
+            (TRY_ALLOC_START..end)
+                .step_by(0x1000)
+                .find(|&base| self.is_range_free(&regions, base, len))
+                .ok_or(Error::OutOfMemory)?
+        };
 
-            return Ok(base);
-        }
+        // The pages are only allocated once touched, see [AddressSpace::handle_fault]
+        regions.insert(VirtualMemoryRegion {
+            start: base,
+            page_count: len,
+            attrs,
+        })?;
 
-        Err(Error::OutOfMemory)
+        Ok(base)
     }
 
     fn deallocate(&self, addr: usize, len: usize) -> Result<(), Error> {
-        for page in (addr..addr + len).step_by(0x1000) {
-            let Some(_phys) = self.translate(page) else {
-                todo!();
+        let end = addr
+            .checked_add(len)
+            .filter(|&end| end <= USER_VIRT_LIMIT)
+            .ok_or(Error::InvalidMemoryOperation)?;
+
+        self.regions.lock().remove(addr, end);
+
+        for page in (addr..end).step_by(0x1000) {
+            // Not yet touched
+            let Some(phys) = self.translate(page) else {
+                continue;
             };
 
             self.write_entry(page, PageEntry::INVALID, true)?;
+            tlb_flush_vaae1(page);
+
+            unsafe {
+                phys::free_page(phys);
+            }
         }
 
         Ok(())
@@ -368,7 +401,76 @@ impl AddressSpace {
             }
         }
 
-        Ok(Self { l1, asid })
+        Ok(Self {
+            l1,
+            asid,
+            regions: IrqSafeSpinlock::new(VirtualMemoryRegionList::new()),
+        })
+    }
+
+    /// Checks that the `base..base + len` range is accessible from userspace, i.e. each of its
+    /// pages is either mapped or reserved for lazy allocation, and that it is writable if `write`
+    /// is set
+    pub fn validate_user_range(&self, base: usize, len: usize, write: bool) -> Result<(), Error> {
+        let end = base
+            .checked_add(len)
+            .filter(|&end| end <= USER_VIRT_LIMIT)
+            .ok_or(Error::InvalidMemoryOperation)?;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        let regions = self.regions.lock();
+
+        for page in ((base & !0xFFF)..end).step_by(0x1000) {
+            let attrs = if let Some(entry) = self.read_entry(page).filter(|e| e.as_page().is_some())
+            {
+                entry.attributes()
+            } else if let Some(region) = regions.find(page) {
+                region.attrs
+            } else {
+                return Err(Error::InvalidMemoryOperation);
+            };
+
+            // EL0 access bit
+            if !attrs.contains(PageAttributes::AP_BOTH_READWRITE) {
+                return Err(Error::InvalidMemoryOperation);
+            }
+
+            if write
+                && attrs.contains(PageAttributes::AP_BOTH_READONLY)
+                && !attrs.contains(PageAttributes::COW)
+            {
+                return Err(Error::InvalidMemoryOperation);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_range_free(&self, regions: &VirtualMemoryRegionList, base: usize, len: usize) -> bool {
+        regions.is_free(base, base + len * 0x1000)
+            && (0..len).all(|i| self.translate(base + i * 0x1000).is_none())
+    }
+
+    /// Resolves a translation fault at `addr` by backing the page with zeroed physical memory,
+    /// if the address belongs to one of the regions reserved in the address space
+    pub fn handle_fault(&self, addr: usize) -> Result<(), Error> {
+        let page = addr & !0xFFF;
+        let regions = self.regions.lock();
+        let region = regions.find(page).ok_or(Error::InvalidMemoryOperation)?;
+
+        if self.translate(page).is_some() {
+            // Already resolved by someone else
+            return Ok(());
+        }
+
+        let phys = phys::alloc_page(PageUsage::Used)?;
+        unsafe {
+            core::slice::from_raw_parts_mut(phys.virtualize() as *mut u8, 0x1000).fill(0);
+        }
+        self.map_page(page, phys, region.attrs)
     }
 
     unsafe fn as_mut(&self) -> &'static mut PageTable<L1> {
@@ -388,6 +490,18 @@ impl AddressSpace {
         l3[l3i].as_page()
     }
 
+    // Read a single 4KiB entry
+    fn read_entry(&self, virt: usize) -> Option<PageEntry<L3>> {
+        let l1i = L1::index(virt);
+        let l2i = L2::index(virt);
+        let l3i = L3::index(virt);
+
+        let l2 = unsafe { self.as_mut().get_mut(l1i) }?;
+        let l3 = l2.get_mut(l2i)?;
+
+        Some(l3[l3i])
+    }
+
     // Write a single 4KiB entry
     fn write_entry(&self, virt: usize, entry: PageEntry<L3>, overwrite: bool) -> Result<(), Error> {
         let l1i = L1::index(virt);
@@ -410,6 +524,135 @@ impl AddressSpace {
         self.write_entry(virt, PageEntry::page(phys, attrs), true)
     }
 
+    /// Resolves a permission fault caused by a write to a copy-on-write page at `addr` by
+    /// giving the address space its own writable copy of the page
+    pub fn handle_cow_fault(&self, addr: usize) -> Result<(), Error> {
+        let page = addr & !0xFFF;
+        let entry = self.read_entry(page).ok_or(Error::InvalidMemoryOperation)?;
+        let phys = entry.as_page().ok_or(Error::InvalidMemoryOperation)?;
+        let attrs = entry.attributes();
+
+        if !attrs.contains(PageAttributes::COW) {
+            return Err(Error::InvalidMemoryOperation);
+        }
+
+        let attrs = (attrs - PageAttributes::COW - PageAttributes::AP_BOTH_READONLY)
+            | PageAttributes::AP_BOTH_READWRITE;
+
+        if phys::page_refcount(phys) == 1 {
+            // Last one holding the page, no need to copy
+            self.map_page(page, phys, attrs)?;
+        } else {
+            let copy = phys::alloc_page(PageUsage::Used)?;
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    phys.virtualize() as *const u8,
+                    copy.virtualize() as *mut u8,
+                    0x1000,
+                );
+            }
+            self.map_page(page, copy, attrs)?;
+
+            unsafe {
+                phys::free_page(phys);
+            }
+        }
+
+        tlb_flush_vaae1(page);
+
+        Ok(())
+    }
+
+    /// Creates a copy of the address space which shares all the pages with this one. Writable
+    /// pages are made read-only in both of the spaces and get copied once written to.
+    pub fn fork(&self) -> Result<Self, Error> {
+        let space = Self::new_empty()?;
+        let l1 = unsafe { self.as_mut() };
+
+        for l1i in 0..512 {
+            let Some(l2) = l1.get_mut(l1i) else {
+                continue;
+            };
+
+            for l2i in 0..512 {
+                let Some(l3) = l2.get_mut(l2i) else {
+                    continue;
+                };
+
+                for l3i in 0..512 {
+                    let entry = l3[l3i];
+                    let Some(phys) = entry.as_page() else {
+                        continue;
+                    };
+                    let virt = (l1i << 30) | (l2i << 21) | (l3i << 12);
+                    let mut attrs = entry.attributes();
+
+                    if attrs.contains(PageAttributes::AP_BOTH_READWRITE)
+                        && !attrs.contains(PageAttributes::AP_BOTH_READONLY)
+                    {
+                        attrs = (attrs - PageAttributes::AP_BOTH_READWRITE)
+                            | PageAttributes::AP_BOTH_READONLY
+                            | PageAttributes::COW;
+                        l3[l3i] = PageEntry::page(phys, attrs);
+                    }
+
+                    if let Err(e) = space.map_page(virt, phys, attrs) {
+                        // Only the pages mapped so far hold a reference for the new space
+                        unsafe {
+                            space.release();
+                        }
+                        tlb_flush_asid(self.asid);
+                        return Err(e);
+                    }
+                    phys::add_page_ref(phys);
+                }
+            }
+        }
+
+        tlb_flush_asid(self.asid);
+
+        *space.regions.lock() = self.regions.lock().clone();
+
+        Ok(space)
+    }
+
+    /// Releases all the pages mapped into the address space along with the translation tables
+    /// themselves.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the address space is not active on any CPU and will not be used
+    /// afterwards.
+    pub unsafe fn release(&self) {
+        let l1 = self.as_mut();
+
+        for l1i in 0..512 {
+            let Some(l2) = l1.get_mut(l1i) else {
+                continue;
+            };
+
+            for l2i in 0..512 {
+                let Some(l3) = l2.get_mut(l2i) else {
+                    continue;
+                };
+
+                for l3i in 0..512 {
+                    if let Some(page) = l3[l3i].as_page() {
+                        phys::free_page(page);
+                    }
+                }
+
+                phys::free_page(l3.physical_address());
+            }
+
+            phys::free_page(l2.physical_address());
+        }
+
+        phys::free_page(l1.physical_address());
+
+        tlb_flush_asid(self.asid);
+    }
+
     /// Returns the physical address of the address space (to be used in a TTBRn_ELx)
     pub fn physical_address(&self) -> usize {
         unsafe { (self.l1 as usize).physicalize() | ((self.asid as usize) << 48) }
@@ -424,6 +667,16 @@ pub fn tlb_flush_vaae1(page: usize) {
     }
 }
 
+/// Flushes all the TLB entries tagged with the ASID
+pub fn tlb_flush_asid(asid: u8) {
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst; tlbi aside1, {asid}; dsb ish; isb",
+            asid = in(reg) (asid as usize) << 48
+        );
+    }
+}
+
 /// Initializes mappings for the kernel and device memory tables.
 ///
 /// # Safety
@@ -445,6 +698,8 @@ pub unsafe fn init_fixed_tables() {
     );
 }
 
+/// Upper bound of the userspace (TTBR0) part of the virtual address space
+pub const USER_VIRT_LIMIT: usize = 1 << 39;
 /// Offset applied to device virtual memory mappings
 pub const DEVICE_VIRT_OFFSET: usize = KERNEL_VIRT_OFFSET + (256 << 30);
 /// Global kernel address space translation tables
@@ -0,0 +1,56 @@
+//! Fault-tolerant copying between kernel and userspace memory
+use core::arch::global_asm;
+
+use abi::error::Error;
+
+use crate::mem::table::USER_VIRT_LIMIT;
+
+extern "C" {
+    fn __aa64_copy_user(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn __aa64_copy_user_fault();
+    fn __aa64_copy_user_end();
+}
+
+fn check_user_range(base: usize, len: usize) -> Result<(), Error> {
+    match base.checked_add(len) {
+        Some(end) if end <= USER_VIRT_LIMIT => Ok(()),
+        _ => Err(Error::InvalidMemoryOperation),
+    }
+}
+
+/// Copies `dst.len()` bytes from userspace address `src` into `dst`. Faults during the copy are
+/// reported as [Error::InvalidMemoryOperation].
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> Result<(), Error> {
+    check_user_range(src, dst.len())?;
+
+    match unsafe { __aa64_copy_user(dst.as_mut_ptr(), src as *const u8, dst.len()) } {
+        0 => Ok(()),
+        _ => Err(Error::InvalidMemoryOperation),
+    }
+}
+
+/// Copies `src` to userspace address `dst`. Faults during the copy are reported as
+/// [Error::InvalidMemoryOperation].
+pub fn copy_to_user(dst: usize, src: &[u8]) -> Result<(), Error> {
+    check_user_range(dst, src.len())?;
+
+    match unsafe { __aa64_copy_user(dst as *mut u8, src.as_ptr(), src.len()) } {
+        0 => Ok(()),
+        _ => Err(Error::InvalidMemoryOperation),
+    }
+}
+
+/// Returns the address execution has to resume at if a fault at `pc` happened during a user
+/// memory copy
+pub(super) fn fault_fixup_address(pc: usize) -> Option<usize> {
+    let start = __aa64_copy_user as *const () as usize;
+    let end = __aa64_copy_user_end as *const () as usize;
+
+    if (start..end).contains(&pc) {
+        Some(__aa64_copy_user_fault as *const () as usize)
+    } else {
+        None
+    }
+}
+
+global_asm!(include_str!("usercopy.S"));
@@ -25,13 +25,15 @@ pub trait TtyDevice<const N: usize>: SerialDevice {
     /// Returns the ring buffer associated with the device
     fn ring(&self) -> &CharRing<N>;
 
-    /// Returns `true` if data is ready to be read from or written to the terminal
+    /// Returns `true` if data is ready to be read from or written to the terminal. Otherwise,
+    /// subscribes the pending wait of the current task to be notified once it is.
     fn is_ready(&self, write: bool) -> Result<bool, Error> {
         let ring = self.ring();
         if write {
-            todo!();
+            // Output is sent synchronously
+            Ok(true)
         } else {
-            Ok(ring.is_readable())
+            Ok(ring.poll_read())
         }
     }
 
@@ -47,14 +49,14 @@ pub trait TtyDevice<const N: usize>: SerialDevice {
     }
 
     /// Reads and processes data from the terminal
-    fn line_read(&'static self, data: &mut [u8]) -> Result<usize, Error> {
+    fn line_read(&'static self, blocking: bool, data: &mut [u8]) -> Result<usize, Error> {
         let ring = self.ring();
 
         if data.is_empty() {
             return Ok(0);
         }
 
-        let byte = ring.getc()?;
+        let byte = ring.getc(blocking)?;
         data[0] = byte;
         Ok(1)
     }
@@ -83,6 +85,11 @@ impl<const N: usize> CharRingInner<N> {
         }
     }
 
+    #[inline]
+    const fn is_writable(&self) -> bool {
+        (self.wr + 1) % N != self.rd
+    }
+
     #[inline]
     unsafe fn read_unchecked(&mut self) -> u8 {
         let res = self.data[self.rd];
@@ -112,17 +119,27 @@ impl<const N: usize> CharRing<N> {
         }
     }
 
-    /// Returns `true` if the buffer has data to read
-    pub fn is_readable(&self) -> bool {
+    /// Returns `true` if the buffer has data to read, otherwise subscribes the pending wait of the
+    /// current task to the buffer's read notifications
+    pub fn poll_read(&self) -> bool {
         let inner = self.inner.lock();
-        inner.is_readable() || inner.flags != 0
+        if inner.is_readable() || inner.flags != 0 {
+            true
+        } else {
+            self.wait_read.subscribe();
+            false
+        }
     }
 
-    /// Reads a single character from the buffer, blocking until available
-    pub fn getc(&'static self) -> Result<u8, Error> {
+    /// Reads a single character from the buffer, blocking until available. Non-blocking reads
+    /// fail with [Error::WouldBlock] instead.
+    pub fn getc(&'static self, blocking: bool) -> Result<u8, Error> {
         let mut lock = self.inner.lock();
         loop {
             if !lock.is_readable() && lock.flags == 0 {
+                if !blocking {
+                    return Err(Error::WouldBlock);
+                }
                 drop(lock);
                 self.wait_read.wait(None)?;
                 lock = self.inner.lock();
@@ -133,23 +150,28 @@ impl<const N: usize> CharRing<N> {
 
         let byte = unsafe { lock.read_unchecked() };
         drop(lock);
-        self.wait_write.wakeup_one();
-        // TODO WAIT_SELECT
+        self.wait_write.wakeup_all();
         Ok(byte)
     }
 
-    /// Sends a single character to the buffer
+    /// Sends a single character to the buffer, blocking until there's space for it. Non-blocking
+    /// writes fail with [Error::WouldBlock] instead.
     pub fn putc(&self, ch: u8, blocking: bool) -> Result<(), Error> {
         let mut lock = self.inner.lock();
-        if blocking {
-            todo!();
+        while !lock.is_writable() {
+            if !blocking {
+                return Err(Error::WouldBlock);
+            }
+            drop(lock);
+            self.wait_write.wait(None)?;
+            lock = self.inner.lock();
         }
         unsafe {
             lock.write_unchecked(ch);
         }
         drop(lock);
-        self.wait_read.wakeup_one();
-        // TODO WAIT_SELECT
+        // Pollers share the channel with blocked readers, so all of them have to be notified
+        self.wait_read.wakeup_all();
         Ok(())
     }
 }
@@ -1,9 +1,13 @@
 //! Device virtual file system
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{
+    any::Any,
+    cell::Ref,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
 use abi::error::Error;
-use alloc::{boxed::Box, format, string::String};
-use vfs::{CharDevice, CharDeviceWrapper, Vnode, VnodeKind, VnodeRef};
+use alloc::{boxed::Box, format, string::String, sync::Arc};
+use vfs::{BlockDevice, CharDevice, CharDeviceWrapper, Filesystem, Vnode, VnodeKind, VnodeRef};
 
 use crate::util::OneTimeInit;
 
@@ -14,8 +18,25 @@ pub enum CharDeviceType {
     TtySerial,
 }
 
+/// Device filesystem instance. All the instances share the same device tree.
+pub struct DevFs;
+
 static DEVFS_ROOT: OneTimeInit<VnodeRef> = OneTimeInit::new();
 
+impl Filesystem for DevFs {
+    fn root(self: Arc<Self>) -> Result<VnodeRef, Error> {
+        Ok(DEVFS_ROOT.get().clone())
+    }
+
+    fn dev(self: Arc<Self>) -> Option<&'static dyn BlockDevice> {
+        None
+    }
+
+    fn data(&self) -> Option<Ref<'_, dyn Any>> {
+        None
+    }
+}
+
 /// Sets up the device filesystem
 pub fn init() {
     let node = Vnode::new("", VnodeKind::Directory);
@@ -32,10 +53,13 @@ pub fn root() -> &'static VnodeRef {
 }
 
 fn _add_char_device(dev: &'static dyn CharDevice, name: String) -> Result<(), Error> {
+    static DEVICE_COUNT: AtomicU64 = AtomicU64::new(1);
+
     infoln!("Add char device: {}", name);
 
+    let device_id = DEVICE_COUNT.fetch_add(1, Ordering::AcqRel);
     let node = Vnode::new(name, VnodeKind::Char);
-    node.set_data(Box::new(CharDeviceWrapper::new(dev)));
+    node.set_data(Box::new(CharDeviceWrapper::new(dev, device_id)));
 
     DEVFS_ROOT.get().add_child(node);
 
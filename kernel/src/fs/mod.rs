@@ -1,3 +1,30 @@
 //! Filesystem implementations
 
+use abi::error::Error;
+use alloc::sync::Arc;
+use vfs::Filesystem;
+
+use crate::util::OneTimeInit;
+
 pub mod devfs;
+pub mod tar;
+pub mod tmpfs;
+
+/// Initial ramdisk image passed to the kernel by the loader. The root filesystem refers to the
+/// image in place, so its pages stay reserved for the whole lifetime of the kernel.
+pub struct Initrd {
+    /// Contents of the image
+    pub data: &'static [u8],
+}
+
+/// Holds the initial ramdisk, if one was provided
+pub static INITRD_DATA: OneTimeInit<Initrd> = OneTimeInit::new();
+
+/// Creates a new instance of a filesystem by its name
+pub fn create_filesystem(name: &str) -> Result<Arc<dyn Filesystem>, Error> {
+    match name {
+        "devfs" => Ok(Arc::new(devfs::DevFs)),
+        "tmpfs" => Ok(tmpfs::create()),
+        _ => Err(Error::NotSupported),
+    }
+}
@@ -0,0 +1,405 @@
+//! Read-only filesystem backed by an in-memory ustar archive
+use core::{any::Any, cell::Ref, time::Duration};
+
+use abi::{
+    error::Error,
+    io::{FileMetadata, FileMode, OpenFlags},
+};
+use alloc::{boxed::Box, string::String, sync::Arc};
+use vfs::{BlockDevice, Filesystem, Vnode, VnodeImpl, VnodeKind, VnodeRef};
+
+const BLOCK_SIZE: usize = 512;
+
+/// Single 512-byte header block of the archive
+struct TarHeader<'a> {
+    data: &'a [u8],
+}
+
+/// Regular file, whose contents are referenced directly from the archive
+struct FileNode {
+    data: &'static [u8],
+    metadata: EntryMetadata,
+}
+
+/// Directory node. Directories which are only implied by the paths of other entries have no
+/// metadata of their own.
+struct DirectoryNode {
+    metadata: Option<EntryMetadata>,
+}
+
+struct SymlinkNode {
+    target: &'static str,
+    metadata: EntryMetadata,
+}
+
+/// Node metadata recorded in the archive
+#[derive(Clone, Copy)]
+struct EntryMetadata {
+    mode: FileMode,
+    uid: u32,
+    gid: u32,
+    mtime: Duration,
+}
+
+/// Read-only filesystem instance built from an archive
+pub struct TarFs {
+    root: VnodeRef,
+}
+
+impl<'a> TarHeader<'a> {
+    fn is_empty(&self) -> bool {
+        self.data.iter().all(|&b| b == 0)
+    }
+
+    fn is_ustar(&self) -> bool {
+        &self.data[257..262] == b"ustar"
+    }
+
+    // Returns a NUL-terminated string field
+    fn str_field(&self, offset: usize, len: usize) -> Result<&'a str, Error> {
+        let field = &self.data[offset..offset + len];
+        let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+        core::str::from_utf8(&field[..end]).map_err(|_| Error::InvalidFile)
+    }
+
+    // Returns a numeric field encoded in octal
+    fn octal_field(&self, offset: usize, len: usize) -> Result<usize, Error> {
+        let mut value = 0usize;
+
+        for &b in self.data[offset..offset + len].iter() {
+            match b {
+                b'0'..=b'7' => {
+                    value = value
+                        .checked_mul(8)
+                        .and_then(|v| v.checked_add((b - b'0') as usize))
+                        .ok_or(Error::InvalidFile)?;
+                }
+                b' ' | 0 => {
+                    if value != 0 {
+                        break;
+                    }
+                }
+                _ => return Err(Error::InvalidFile),
+            }
+        }
+
+        Ok(value)
+    }
+
+    // The checksum is the sum of the header bytes with the checksum field itself taken as spaces.
+    // Some archivers sum the bytes as signed, so both variants are accepted.
+    fn verify_checksum(&self) -> Result<(), Error> {
+        let expected = self.octal_field(148, 8)?;
+        let (mut unsigned, mut signed) = (0usize, 0isize);
+
+        for (i, &b) in self.data.iter().enumerate() {
+            let b = if (148..156).contains(&i) { b' ' } else { b };
+            unsigned += b as usize;
+            signed += b as i8 as isize;
+        }
+
+        if expected == unsigned || expected as isize == signed {
+            Ok(())
+        } else {
+            Err(Error::InvalidFile)
+        }
+    }
+
+    fn name(&self) -> Result<&'a str, Error> {
+        self.str_field(0, 100)
+    }
+
+    fn prefix(&self) -> Result<&'a str, Error> {
+        if self.is_ustar() {
+            self.str_field(345, 155)
+        } else {
+            Ok("")
+        }
+    }
+
+    fn link_name(&self) -> Result<&'a str, Error> {
+        self.str_field(157, 100)
+    }
+
+    fn size(&self) -> Result<usize, Error> {
+        self.octal_field(124, 12)
+    }
+
+    fn type_flag(&self) -> u8 {
+        self.data[156]
+    }
+
+    fn metadata(&self) -> Result<EntryMetadata, Error> {
+        Ok(EntryMetadata {
+            mode: FileMode::new(self.octal_field(100, 8)? as u32),
+            uid: self.octal_field(108, 8)? as u32,
+            gid: self.octal_field(116, 8)? as u32,
+            mtime: Duration::from_secs(self.octal_field(136, 12)? as u64),
+        })
+    }
+}
+
+impl EntryMetadata {
+    fn fill(&self, metadata: &mut FileMetadata) {
+        metadata.mode = self.mode;
+        metadata.uid = self.uid;
+        metadata.gid = self.gid;
+        metadata.ctime = self.mtime;
+        metadata.mtime = self.mtime;
+        metadata.atime = self.mtime;
+    }
+}
+
+impl VnodeImpl for FileNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::NotADirectory)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, opts: OpenFlags) -> Result<usize, Error> {
+        if opts.is_write() {
+            return Err(Error::ReadOnlyFs);
+        }
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Error> {
+        if pos >= self.data.len() {
+            return Ok(0);
+        }
+
+        let count = core::cmp::min(data.len(), self.data.len() - pos);
+        data[..count].copy_from_slice(&self.data[pos..pos + count]);
+
+        Ok(count)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn truncate(&mut self, _node: &VnodeRef, _size: usize) -> Result<(), Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn size(&mut self, _node: &VnodeRef) -> Result<usize, Error> {
+        Ok(self.data.len())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.size = self.data.len() as u64;
+        self.metadata.fill(metadata);
+        Ok(())
+    }
+}
+
+impl VnodeImpl for DirectoryNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, _pos: usize, _data: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn remove(&mut self, _at: &VnodeRef, _name: &str) -> Result<(), Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn rename(
+        &mut self,
+        _at: &VnodeRef,
+        _name: &str,
+        _new_parent: &VnodeRef,
+        _new_name: &str,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        if let Some(entry) = &self.metadata {
+            entry.fill(metadata);
+        }
+        Ok(())
+    }
+}
+
+impl VnodeImpl for SymlinkNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::NotADirectory)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+        Err(Error::InvalidFile)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, _pos: usize, _data: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::InvalidFile)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::ReadOnlyFs)
+    }
+
+    fn read_link(&mut self, _node: &VnodeRef) -> Result<String, Error> {
+        Ok(self.target.into())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.size = self.target.len() as u64;
+        self.metadata.fill(metadata);
+        Ok(())
+    }
+}
+
+fn make_directory(name: &str) -> VnodeRef {
+    let node = Vnode::new(name, VnodeKind::Directory);
+    node.set_data(Box::new(DirectoryNode { metadata: None }));
+    node
+}
+
+// Returns the child directory with given name, creating it if it's not present yet
+fn get_or_create_directory(parent: &VnodeRef, name: &str) -> Result<VnodeRef, Error> {
+    if let Some(node) = parent.lookup(name) {
+        if !node.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+        return Ok(node);
+    }
+
+    let node = make_directory(name);
+    parent.add_child(node.clone());
+    Ok(node)
+}
+
+fn add_entry(
+    root: &VnodeRef,
+    header: &TarHeader<'static>,
+    data: &'static [u8],
+) -> Result<(), Error> {
+    let prefix = header.prefix()?;
+    let name = header.name()?;
+
+    let mut components = prefix
+        .split('/')
+        .chain(name.split('/'))
+        .filter(|c| !c.is_empty() && *c != ".")
+        .peekable();
+
+    let mut parent = root.clone();
+    while let Some(component) = components.next() {
+        if component == ".." {
+            return Err(Error::InvalidFile);
+        }
+
+        if components.peek().is_some() {
+            parent = get_or_create_directory(&parent, component)?;
+            continue;
+        }
+
+        if header.type_flag() != b'5' && parent.lookup(component).is_some() {
+            return Err(Error::AlreadyExists);
+        }
+
+        let metadata = header.metadata()?;
+
+        match header.type_flag() {
+            // Regular file
+            0 | b'0' => {
+                let node = Vnode::new(component, VnodeKind::Regular);
+                node.set_data(Box::new(FileNode { data, metadata }));
+                parent.add_child(node);
+            }
+            // Symbolic link
+            b'2' => {
+                let node = Vnode::new(component, VnodeKind::Symlink);
+                node.set_data(Box::new(SymlinkNode {
+                    target: header.link_name()?,
+                    metadata,
+                }));
+                parent.add_child(node);
+            }
+            // Directory
+            b'5' => {
+                let node = get_or_create_directory(&parent, component)?;
+                node.set_data(Box::new(DirectoryNode {
+                    metadata: Some(metadata),
+                }));
+            }
+            kind => {
+                warnln!(
+                    "tar: skipping {:?} of unsupported type {:?}",
+                    component,
+                    kind as char
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Filesystem for TarFs {
+    fn root(self: Arc<Self>) -> Result<VnodeRef, Error> {
+        Ok(self.root.clone())
+    }
+
+    fn dev(self: Arc<Self>) -> Option<&'static dyn BlockDevice> {
+        None
+    }
+
+    fn data(&self) -> Option<Ref<'_, dyn Any>> {
+        None
+    }
+}
+
+/// Builds a read-only filesystem from a ustar archive
+pub fn create(data: &'static [u8]) -> Result<Arc<TarFs>, Error> {
+    let root = make_directory("");
+    let mut offset = 0;
+
+    while offset + BLOCK_SIZE <= data.len() {
+        let header = TarHeader {
+            data: &data[offset..offset + BLOCK_SIZE],
+        };
+
+        // Archive is terminated by zero blocks
+        if header.is_empty() {
+            break;
+        }
+
+        header.verify_checksum()?;
+
+        let size = header.size()?;
+        let data_start = offset + BLOCK_SIZE;
+        let data_end = data_start
+            .checked_add(size)
+            .filter(|&end| end <= data.len())
+            .ok_or(Error::InvalidFile)?;
+
+        add_entry(&root, &header, &data[data_start..data_end])?;
+
+        offset = data_start + (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+    }
+
+    Ok(Arc::new(TarFs { root }))
+}
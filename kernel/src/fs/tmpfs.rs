@@ -0,0 +1,359 @@
+//! In-memory temporary filesystem
+use core::{any::Any, cell::Ref, time::Duration};
+
+use abi::{
+    error::Error,
+    io::{FileMetadata, OpenFlags},
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use vfs::{BlockDevice, Filesystem, Vnode, VnodeImpl, VnodeKind, VnodeRef};
+
+use crate::{
+    arch::PLATFORM,
+    device::platform::Platform,
+    mem::{
+        phys::{self, PageUsage},
+        ConvertAddress,
+    },
+};
+
+const PAGE_SIZE: usize = 0x1000;
+// Files are backed by physical memory, so their size is bounded to keep a single write from
+// exhausting it
+const MAX_FILE_SIZE: usize = 0x4000000;
+
+/// Regular file contents, stored in physical pages. Bytes past the end of the file are always
+/// kept zeroed, so the file can be extended without clearing the pages again.
+struct FileNode {
+    pages: Vec<usize>,
+    size: usize,
+    times: Timestamps,
+}
+
+struct DirectoryNode {
+    times: Timestamps,
+}
+
+struct SymlinkNode {
+    target: String,
+    times: Timestamps,
+}
+
+#[derive(Clone, Copy)]
+struct Timestamps {
+    ctime: Duration,
+    mtime: Duration,
+    atime: Duration,
+}
+
+/// In-memory filesystem instance
+pub struct TmpFs {
+    root: VnodeRef,
+}
+
+fn now() -> Duration {
+    PLATFORM
+        .timestamp_source()
+        .timestamp()
+        .unwrap_or(Duration::ZERO)
+}
+
+impl Timestamps {
+    fn new() -> Self {
+        let now = now();
+        Self {
+            ctime: now,
+            mtime: now,
+            atime: now,
+        }
+    }
+
+    fn modify(&mut self) {
+        self.mtime = now();
+        self.ctime = self.mtime;
+    }
+
+    fn fill(&self, metadata: &mut FileMetadata) {
+        metadata.ctime = self.ctime;
+        metadata.mtime = self.mtime;
+        metadata.atime = self.atime;
+    }
+}
+
+impl FileNode {
+    fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            size: 0,
+            times: Timestamps::new(),
+        }
+    }
+
+    fn page_data(&self, index: usize) -> &'static mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self.pages[index].virtualize() as *mut u8, PAGE_SIZE)
+        }
+    }
+
+    fn resize(&mut self, size: usize) -> Result<(), Error> {
+        if size > MAX_FILE_SIZE {
+            return Err(Error::NoSpace);
+        }
+
+        let page_count = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        let old_page_count = self.pages.len();
+
+        if old_page_count < page_count {
+            self.pages
+                .try_reserve(page_count - old_page_count)
+                .map_err(|_| Error::OutOfMemory)?;
+        }
+
+        while self.pages.len() < page_count {
+            let page = match phys::alloc_page(PageUsage::Used) {
+                Ok(page) => page,
+                Err(e) => {
+                    // Give back whatever was added so the file is left as it was
+                    for page in self.pages.drain(old_page_count..) {
+                        unsafe {
+                            phys::free_page(page);
+                        }
+                    }
+                    return Err(e);
+                }
+            };
+            self.pages.push(page);
+            self.page_data(self.pages.len() - 1).fill(0);
+        }
+
+        if size < self.size {
+            while self.pages.len() > page_count {
+                let page = self.pages.pop().unwrap();
+                unsafe {
+                    phys::free_page(page);
+                }
+            }
+
+            // Clear the tail of the last remaining page
+            if size % PAGE_SIZE != 0 {
+                self.page_data(page_count - 1)[size % PAGE_SIZE..].fill(0);
+            }
+        }
+
+        self.size = size;
+        Ok(())
+    }
+}
+
+impl VnodeImpl for FileNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::NotADirectory)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Error> {
+        if pos >= self.size {
+            return Ok(0);
+        }
+
+        let count = core::cmp::min(data.len(), self.size - pos);
+        let mut offset = 0;
+
+        while offset < count {
+            let page_offset = (pos + offset) % PAGE_SIZE;
+            let amount = core::cmp::min(count - offset, PAGE_SIZE - page_offset);
+            let page = self.page_data((pos + offset) / PAGE_SIZE);
+
+            data[offset..offset + amount].copy_from_slice(&page[page_offset..page_offset + amount]);
+            offset += amount;
+        }
+
+        self.times.atime = now();
+
+        Ok(count)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Error> {
+        let end = pos.checked_add(data.len()).ok_or(Error::InvalidArgument)?;
+        if end > self.size {
+            self.resize(end)?;
+        }
+
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let page_offset = (pos + offset) % PAGE_SIZE;
+            let amount = core::cmp::min(data.len() - offset, PAGE_SIZE - page_offset);
+            let page = self.page_data((pos + offset) / PAGE_SIZE);
+
+            page[page_offset..page_offset + amount].copy_from_slice(&data[offset..offset + amount]);
+            offset += amount;
+        }
+
+        self.times.modify();
+
+        Ok(data.len())
+    }
+
+    fn truncate(&mut self, _node: &VnodeRef, size: usize) -> Result<(), Error> {
+        self.resize(size)?;
+        self.times.modify();
+        Ok(())
+    }
+
+    fn size(&mut self, _node: &VnodeRef) -> Result<usize, Error> {
+        Ok(self.size)
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.size = self.size as u64;
+        self.times.fill(metadata);
+        Ok(())
+    }
+}
+
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        for &page in self.pages.iter() {
+            unsafe {
+                phys::free_page(page);
+            }
+        }
+    }
+}
+
+impl DirectoryNode {
+    fn new() -> Self {
+        Self {
+            times: Timestamps::new(),
+        }
+    }
+}
+
+impl VnodeImpl for DirectoryNode {
+    fn create(&mut self, _at: &VnodeRef, name: &str, kind: VnodeKind) -> Result<VnodeRef, Error> {
+        let node = Vnode::new(name, kind);
+
+        match kind {
+            VnodeKind::Directory => node.set_data(Box::new(DirectoryNode::new())),
+            VnodeKind::Regular => node.set_data(Box::new(FileNode::new())),
+            _ => return Err(Error::NotSupported),
+        }
+
+        self.times.modify();
+
+        Ok(node)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, _pos: usize, _data: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::IsADirectory)
+    }
+
+    fn symlink(&mut self, _at: &VnodeRef, name: &str, target: &str) -> Result<VnodeRef, Error> {
+        let node = Vnode::new(name, VnodeKind::Symlink);
+        node.set_data(Box::new(SymlinkNode {
+            target: target.into(),
+            times: Timestamps::new(),
+        }));
+
+        self.times.modify();
+
+        Ok(node)
+    }
+
+    fn remove(&mut self, _at: &VnodeRef, _name: &str) -> Result<(), Error> {
+        // Node contents are released once the last reference to the vnode is dropped
+        self.times.modify();
+        Ok(())
+    }
+
+    fn rename(
+        &mut self,
+        _at: &VnodeRef,
+        _name: &str,
+        _new_parent: &VnodeRef,
+        _new_name: &str,
+    ) -> Result<(), Error> {
+        self.times.modify();
+        Ok(())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        self.times.fill(metadata);
+        Ok(())
+    }
+}
+
+impl VnodeImpl for SymlinkNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::NotADirectory)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+        Err(Error::InvalidFile)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, _pos: usize, _data: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::InvalidFile)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::InvalidFile)
+    }
+
+    fn read_link(&mut self, _node: &VnodeRef) -> Result<String, Error> {
+        self.times.atime = now();
+        Ok(self.target.clone())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.size = self.target.len() as u64;
+        self.times.fill(metadata);
+        Ok(())
+    }
+}
+
+impl Filesystem for TmpFs {
+    fn root(self: Arc<Self>) -> Result<VnodeRef, Error> {
+        Ok(self.root.clone())
+    }
+
+    fn dev(self: Arc<Self>) -> Option<&'static dyn BlockDevice> {
+        None
+    }
+
+    fn data(&self) -> Option<Ref<'_, dyn Any>> {
+        None
+    }
+}
+
+/// Creates an empty tmpfs instance
+pub fn create() -> Arc<TmpFs> {
+    let root = Vnode::new("", VnodeKind::Directory);
+    root.set_data(Box::new(DirectoryNode::new()));
+
+    Arc::new(TmpFs { root })
+}
@@ -13,11 +13,15 @@
 #![no_std]
 #![no_main]
 
-use abi::io::{OpenFlags, RawFd};
+use abi::{
+    error::Error,
+    io::{OpenFlags, RawFd},
+};
+use alloc::sync::Arc;
 use task::process::Process;
-use vfs::IoContext;
+use vfs::{Filesystem, IoContext, VnodeKind, VnodeRef};
 
-use crate::fs::devfs;
+use crate::fs::{devfs, tar, tmpfs, INITRD_DATA};
 
 extern crate alloc;
 
@@ -36,6 +40,33 @@ pub mod syscall;
 pub mod task;
 pub mod util;
 
+// Root filesystem is the initrd when one is provided, with the devices mounted under /dev
+fn setup_root() -> Result<VnodeRef, Error> {
+    let root_fs: Arc<dyn Filesystem> = if INITRD_DATA.is_initialized() {
+        tar::create(INITRD_DATA.get().data)?
+    } else {
+        warnln!("No initrd provided, falling back to an empty tmpfs root");
+        tmpfs::create()
+    };
+    let root = root_fs.root()?;
+
+    // A read-only root without /dev has nowhere to mount devfs, which is not fatal: the kernel
+    // itself reaches the devices through devfs::root()
+    let dev = match root.lookup_or_load("dev") {
+        Err(Error::DoesNotExist) => match root.create("dev", VnodeKind::Directory) {
+            Err(Error::ReadOnlyFs) => {
+                warnln!("Root filesystem has no /dev and is read-only, devfs is not mounted");
+                return Ok(root);
+            }
+            res => res?,
+        },
+        res => res?,
+    };
+    dev.mount(Arc::new(devfs::DevFs))?;
+
+    Ok(root)
+}
+
 /// Entry point for common kernel code.
 ///
 /// # Note
@@ -43,20 +74,16 @@ pub mod util;
 /// This function is meant to be used as a kernel-space process after all the platform-specific
 /// initialization has finished.
 pub fn kernel_main() {
-    static USER_PROGRAM: &[u8] = include_bytes!(concat!(
-        "../../target/aarch64-unknown-yggdrasil/",
-        env!("PROFILE"),
-        "/test_program"
-    ));
-
     let devfs_root = devfs::root();
     let tty_node = devfs_root.lookup("ttyS0").unwrap();
 
-    let ioctx = IoContext::new(devfs_root.clone());
+    let root = setup_root().expect("Could not set up the root filesystem");
+    let ioctx = IoContext::new(root);
 
-    // Spawn a test user task
-    let proc =
-        proc::exec::create_from_memory(USER_PROGRAM, &["user-program", "argument 1", "argument 2"]);
+    // Spawn the init process
+    let proc = ioctx
+        .find(None, "/init", true)
+        .and_then(|node| proc::exec::create_from_file(&node, &["/init"]));
 
     match proc {
         Ok(proc) => {
@@ -73,10 +100,11 @@ pub fn kernel_main() {
                 io.set_file(RawFd::STDERR, stderr).unwrap();
             }
 
+            proc.register();
             proc.enqueue_somewhere();
         }
         Err(err) => {
-            warnln!("Failed to create user process: {:?}", err);
+            warnln!("Failed to start init: {:?}", err);
         }
     };
 
@@ -1,51 +1,186 @@
-//! Kernel's global heap allocator
+//! Kernel's global heap allocator.
+//!
+//! Small allocations are served from per-size-class slab caches, each growing by a single page
+//! taken from [crate::mem::phys] whenever it runs out of free objects. Allocations larger than
+//! the biggest size class are backed directly by contiguous physical pages.
 use core::{
     alloc::{GlobalAlloc, Layout},
-    ptr::{null_mut, NonNull},
+    ptr::null_mut,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use linked_list_allocator::Heap;
-use spinning_top::Spinlock;
+use crate::{
+    mem::{
+        phys::{self, PageUsage},
+        ConvertAddress,
+    },
+    sync::IrqSafeSpinlock,
+};
+
+const SLAB_SIZE: usize = 0x1000;
+
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+struct SlabCacheInner {
+    free: *mut FreeObject,
+    slab_count: usize,
+    allocated: usize,
+}
+
+/// Cache of fixed-size objects carved out of page-sized slabs
+struct SlabCache {
+    object_size: usize,
+    inner: IrqSafeSpinlock<SlabCacheInner>,
+}
+
+/// Usage statistics of a single slab cache
+#[derive(Clone, Copy, Debug)]
+pub struct SlabCacheStats {
+    /// Size of the objects the cache holds
+    pub object_size: usize,
+    /// Number of pages taken by the cache
+    pub slab_count: usize,
+    /// Number of objects currently handed out
+    pub allocated: usize,
+}
 
 struct KernelAllocator {
-    inner: Spinlock<Heap>,
+    caches: [SlabCache; 8],
+    large_pages: AtomicUsize,
+}
+
+unsafe impl Send for SlabCacheInner {}
+
+impl SlabCache {
+    const fn new(object_size: usize) -> Self {
+        Self {
+            object_size,
+            inner: IrqSafeSpinlock::new(SlabCacheInner {
+                free: null_mut(),
+                slab_count: 0,
+                allocated: 0,
+            }),
+        }
+    }
+
+    fn alloc(&self) -> *mut u8 {
+        let mut inner = self.inner.lock();
+
+        if inner.free.is_null() {
+            let Ok(page) = phys::alloc_page(PageUsage::Used) else {
+                return null_mut();
+            };
+            let base = unsafe { page.virtualize() };
+
+            // Thread all the objects of the new slab into the free list
+            for offset in (0..SLAB_SIZE).step_by(self.object_size).rev() {
+                let object = (base + offset) as *mut FreeObject;
+                unsafe {
+                    object.write(FreeObject { next: inner.free });
+                }
+                inner.free = object;
+            }
+
+            inner.slab_count += 1;
+        }
+
+        let object = inner.free;
+        inner.free = unsafe { (*object).next };
+        inner.allocated += 1;
+
+        object as *mut u8
+    }
+
+    unsafe fn free(&self, ptr: *mut u8) {
+        let mut inner = self.inner.lock();
+        let object = ptr as *mut FreeObject;
+
+        object.write(FreeObject { next: inner.free });
+        inner.free = object;
+        inner.allocated -= 1;
+    }
+
+    fn stats(&self) -> SlabCacheStats {
+        let inner = self.inner.lock();
+
+        SlabCacheStats {
+            object_size: self.object_size,
+            slab_count: inner.slab_count,
+            allocated: inner.allocated,
+        }
+    }
 }
 
 impl KernelAllocator {
-    const fn empty() -> Self {
+    const fn new() -> Self {
         Self {
-            inner: Spinlock::new(Heap::empty()),
+            caches: [
+                SlabCache::new(16),
+                SlabCache::new(32),
+                SlabCache::new(64),
+                SlabCache::new(128),
+                SlabCache::new(256),
+                SlabCache::new(512),
+                SlabCache::new(1024),
+                SlabCache::new(2048),
+            ],
+            large_pages: AtomicUsize::new(0),
         }
     }
 
-    unsafe fn init(&self, base: usize, size: usize) {
-        self.inner.lock().init(base as _, size);
+    fn cache_for(&self, layout: Layout) -> Option<&SlabCache> {
+        // Objects are naturally aligned to their size within the page-aligned slabs
+        let size = layout.size().max(layout.align());
+        self.caches.iter().find(|cache| cache.object_size >= size)
     }
 }
 
 unsafe impl GlobalAlloc for KernelAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        match self.inner.lock().allocate_first_fit(layout) {
-            Ok(v) => v.as_ptr(),
+        if let Some(cache) = self.cache_for(layout) {
+            return cache.alloc();
+        }
+
+        if layout.align() > SLAB_SIZE {
+            return null_mut();
+        }
+
+        let count = (layout.size() + SLAB_SIZE - 1) / SLAB_SIZE;
+        match phys::alloc_pages_contiguous(count, PageUsage::Used) {
+            Ok(base) => {
+                self.large_pages.fetch_add(count, Ordering::Relaxed);
+                base.virtualize() as *mut u8
+            }
             Err(_) => null_mut(),
         }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        let ptr = NonNull::new(ptr).unwrap();
-        self.inner.lock().deallocate(ptr, layout)
+        if let Some(cache) = self.cache_for(layout) {
+            return cache.free(ptr);
+        }
+
+        let count = (layout.size() + SLAB_SIZE - 1) / SLAB_SIZE;
+        let base = (ptr as usize).physicalize();
+        for i in 0..count {
+            phys::free_page(base + i * SLAB_SIZE);
+        }
+        self.large_pages.fetch_sub(count, Ordering::Relaxed);
     }
 }
 
 #[global_allocator]
-static GLOBAL_HEAP: KernelAllocator = KernelAllocator::empty();
-
-/// Sets up kernel's global heap with given memory range.
-///
-/// # Safety
-///
-/// The caller must ensure the range is valid and mapped virtual memory.
-pub unsafe fn init_heap(heap_base: usize, heap_size: usize) {
-    debugln!("Heap: {:#x}..{:#x}", heap_base, heap_base + heap_size);
-    GLOBAL_HEAP.init(heap_base, heap_size);
+static GLOBAL_HEAP: KernelAllocator = KernelAllocator::new();
+
+/// Prints the usage statistics of the kernel heap
+pub fn dump_stats() {
+    for cache in GLOBAL_HEAP.caches.iter() {
+        debugln!("{:?}", cache.stats());
+    }
+    debugln!(
+        "Large allocations: {} pages",
+        GLOBAL_HEAP.large_pages.load(Ordering::Relaxed)
+    );
 }
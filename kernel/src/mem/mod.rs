@@ -8,6 +8,7 @@ pub mod device;
 pub mod heap;
 pub mod phys;
 pub mod table;
+pub mod vma;
 
 /// Kernel's physical load address
 pub const KERNEL_PHYS_BASE: usize = PlatformImpl::KERNEL_PHYS_BASE;
@@ -5,10 +5,32 @@ use abi::error::Error;
 
 use super::{Page, PageUsage};
 
-/// Physical memory management interface
+/// Largest block order (i.e. blocks of `1 << (MAX_ORDER - 1)` pages) tracked by the allocator
+pub const MAX_ORDER: usize = 11;
+
+const NO_PAGE: u32 = u32::MAX;
+const NOT_A_BLOCK: u8 = u8::MAX;
+
+/// Amount of pages currently in each kind of use
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicalMemoryStats {
+    /// Pages not available for allocation
+    pub reserved: usize,
+    /// Free pages
+    pub available: usize,
+    /// Allocated pages
+    pub used: usize,
+}
+
+/// Physical memory management interface.
+///
+/// Implemented as a binary buddy allocator: free memory is kept as power-of-two blocks of pages
+/// linked into per-order free lists through the page tracking array.
 pub struct PhysicalMemoryManager {
     pages: &'static mut [Page],
     offset: usize,
+    free_lists: [u32; MAX_ORDER],
+    usage_counts: [usize; 3],
 }
 
 impl PhysicalMemoryManager {
@@ -29,25 +51,26 @@ impl PhysicalMemoryManager {
             *page = Page {
                 usage: PageUsage::Reserved,
                 refcount: 0,
+                order: NOT_A_BLOCK,
+                prev: NO_PAGE,
+                next: NO_PAGE,
             };
         }
 
-        PhysicalMemoryManager { pages, offset }
+        let mut usage_counts = [0; 3];
+        usage_counts[PageUsage::Reserved as usize] = page_count;
+
+        PhysicalMemoryManager {
+            pages,
+            offset,
+            free_lists: [NO_PAGE; MAX_ORDER],
+            usage_counts,
+        }
     }
 
     /// Allocates a single page, marking it as used with `usage`
     pub fn alloc_page(&mut self, usage: PageUsage) -> Result<usize, Error> {
-        assert_ne!(usage, PageUsage::Available);
-        assert_ne!(usage, PageUsage::Reserved);
-
-        for index in 0..self.pages.len() {
-            if self.pages[index].usage == PageUsage::Available {
-                self.pages[index].usage = PageUsage::Used;
-                return Ok(index * 4096 + self.offset);
-            }
-        }
-
-        Err(Error::OutOfMemory)
+        self.alloc_contiguous_pages(1, usage)
     }
 
     /// Allocates a contiguous range of physical pages, marking it as used with `usage`
@@ -60,22 +83,88 @@ impl PhysicalMemoryManager {
         assert_ne!(usage, PageUsage::Reserved);
         assert_ne!(count, 0);
 
-        'l0: for i in 0..self.pages.len() {
-            for j in 0..count {
-                if self.pages[i + j].usage != PageUsage::Available {
-                    continue 'l0;
-                }
-            }
-            for j in 0..count {
-                let page = &mut self.pages[i + j];
-                assert!(page.usage == PageUsage::Available);
-                page.usage = usage;
-                page.refcount = 1;
-            }
-            return Ok(self.offset + i * 0x1000);
+        let order = count.next_power_of_two().trailing_zeros() as usize;
+        if order >= MAX_ORDER {
+            return Err(Error::OutOfMemory);
+        }
+
+        // Find the smallest free block which fits the request
+        let mut block_order = order;
+        while block_order < MAX_ORDER && self.free_lists[block_order] == NO_PAGE {
+            block_order += 1;
+        }
+        if block_order == MAX_ORDER {
+            return Err(Error::OutOfMemory);
+        }
+
+        let index = self.free_lists[block_order] as usize;
+        self.unlink(index, block_order);
+
+        // Split the block, returning the upper halves to the free lists
+        while block_order > order {
+            block_order -= 1;
+            self.link(index + (1 << block_order), block_order);
         }
 
-        Err(Error::OutOfMemory)
+        for page in &mut self.pages[index..index + (1 << order)] {
+            page.usage = usage;
+            page.refcount = 1;
+        }
+        self.usage_counts[PageUsage::Available as usize] -= 1 << order;
+        self.usage_counts[usage as usize] += 1 << order;
+
+        // Give back the part of the block which was not requested
+        for i in count..1 << order {
+            self.release(index + i);
+        }
+
+        Ok(self.offset + index * 0x1000)
+    }
+
+    fn page_mut(&mut self, addr: usize) -> &mut Page {
+        assert!(addr >= self.offset);
+        let index = (addr - self.offset) / 4096;
+        let page = &mut self.pages[index];
+
+        assert_ne!(page.usage, PageUsage::Available);
+        assert_ne!(page.usage, PageUsage::Reserved);
+
+        page
+    }
+
+    /// Adds a reference to a previously allocated page.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the address does not point to an allocated page.
+    pub fn add_page_ref(&mut self, addr: usize) {
+        self.page_mut(addr).refcount += 1;
+    }
+
+    /// Returns the number of references to an allocated page.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the address does not point to an allocated page.
+    pub fn page_refcount(&mut self, addr: usize) -> u32 {
+        self.page_mut(addr).refcount
+    }
+
+    /// Drops a reference to a previously allocated page, returning it to the pool of available
+    /// ones once no references remain.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the address does not point to an allocated page.
+    pub fn free_page(&mut self, addr: usize) {
+        let page = self.page_mut(addr);
+
+        assert_ne!(page.refcount, 0);
+        page.refcount -= 1;
+
+        if page.refcount == 0 {
+            self.release((addr - self.offset) / 4096);
+        }
     }
 
     /// Marks a previously reserved page as available.
@@ -91,5 +180,86 @@ impl PhysicalMemoryManager {
         assert_eq!(self.pages[index].refcount, 0);
 
         self.pages[index].usage = PageUsage::Available;
+        self.usage_counts[PageUsage::Reserved as usize] -= 1;
+        self.usage_counts[PageUsage::Available as usize] += 1;
+
+        self.insert_block(index);
+    }
+
+    /// Returns the amount of pages in each kind of use
+    pub fn stats(&self) -> PhysicalMemoryStats {
+        PhysicalMemoryStats {
+            reserved: self.usage_counts[PageUsage::Reserved as usize],
+            available: self.usage_counts[PageUsage::Available as usize],
+            used: self.usage_counts[PageUsage::Used as usize],
+        }
+    }
+
+    // Returns a single allocated page to the free lists
+    fn release(&mut self, index: usize) {
+        let page = &mut self.pages[index];
+        self.usage_counts[page.usage as usize] -= 1;
+        self.usage_counts[PageUsage::Available as usize] += 1;
+
+        page.usage = PageUsage::Available;
+        page.refcount = 0;
+
+        self.insert_block(index);
+    }
+
+    // Inserts a single free page into the free lists, merging it with its free buddies
+    fn insert_block(&mut self, mut index: usize) {
+        let mut order = 0;
+
+        while order < MAX_ORDER - 1 {
+            let buddy = index ^ (1 << order);
+
+            if buddy >= self.pages.len()
+                || self.pages[buddy].usage != PageUsage::Available
+                || self.pages[buddy].order != order as u8
+            {
+                break;
+            }
+
+            self.unlink(buddy, order);
+            index &= !(1 << order);
+            order += 1;
+        }
+
+        self.link(index, order);
+    }
+
+    fn link(&mut self, index: usize, order: usize) {
+        let head = self.free_lists[order];
+
+        if head != NO_PAGE {
+            self.pages[head as usize].prev = index as u32;
+        }
+
+        let page = &mut self.pages[index];
+        page.order = order as u8;
+        page.prev = NO_PAGE;
+        page.next = head;
+
+        self.free_lists[order] = index as u32;
+    }
+
+    fn unlink(&mut self, index: usize, order: usize) {
+        let page = &mut self.pages[index];
+        let (prev, next) = (page.prev, page.next);
+
+        page.order = NOT_A_BLOCK;
+        page.prev = NO_PAGE;
+        page.next = NO_PAGE;
+
+        if prev == NO_PAGE {
+            self.free_lists[order] = next;
+        } else {
+            self.pages[prev as usize].next = next;
+        }
+
+        if next != NO_PAGE {
+            self.pages[next as usize].prev = prev;
+        }
     }
 }
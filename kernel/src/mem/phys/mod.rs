@@ -35,6 +35,10 @@ pub enum PageUsage {
 pub struct Page {
     usage: PageUsage,
     refcount: u32,
+    // Free block bookkeeping, only meaningful for the first page of a free block
+    order: u8,
+    prev: u32,
+    next: u32,
 }
 
 /// Defines an usable memory region
@@ -71,6 +75,27 @@ pub fn alloc_page(usage: PageUsage) -> Result<usize, Error> {
     PHYSICAL_MEMORY.get().lock().alloc_page(usage)
 }
 
+/// Drops a reference to a physical page, returning it to the global manager once no references
+/// remain.
+///
+/// # Safety
+///
+/// The caller must ensure the reference being dropped is no longer used by any mapping or pointer.
+pub unsafe fn free_page(addr: usize) {
+    PHYSICAL_MEMORY.get().lock().free_page(addr)
+}
+
+/// Adds a reference to an allocated physical page, so it is shared until all the references are
+/// dropped with [free_page]
+pub fn add_page_ref(addr: usize) {
+    PHYSICAL_MEMORY.get().lock().add_page_ref(addr)
+}
+
+/// Returns the number of references to an allocated physical page
+pub fn page_refcount(addr: usize) -> u32 {
+    PHYSICAL_MEMORY.get().lock().page_refcount(addr)
+}
+
 /// Allocates a contiguous range of physical pages from the global manager
 pub fn alloc_pages_contiguous(count: usize, usage: PageUsage) -> Result<usize, Error> {
     PHYSICAL_MEMORY
@@ -181,6 +206,7 @@ pub unsafe fn init_from_iter<I: Iterator<Item = PhysicalMemoryRegion> + Clone>(
     }
 
     infoln!("{} available pages", page_count);
+    debugln!("{:?}", manager.stats());
 
     PHYSICAL_MEMORY.init(Spinlock::new(manager));
     Ok(())
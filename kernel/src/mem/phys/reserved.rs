@@ -4,7 +4,7 @@ use crate::util::StaticVector;
 
 use super::PhysicalMemoryRegion;
 
-static mut RESERVED_MEMORY: StaticVector<PhysicalMemoryRegion, 4> = StaticVector::new();
+static mut RESERVED_MEMORY: StaticVector<PhysicalMemoryRegion, 8> = StaticVector::new();
 
 /// Marks a region of physical memory as reserved.
 ///
@@ -1,7 +1,9 @@
 //! Virtual memory table interface
 use abi::error::Error;
 
-pub use crate::arch::aarch64::table::{AddressSpace, PageAttributes, PageEntry, PageTable};
+pub use crate::arch::aarch64::table::{
+    AddressSpace, PageAttributes, PageEntry, PageTable, USER_VIRT_LIMIT,
+};
 
 /// Interface for virtual memory address space management
 pub trait VirtualMemoryManager {
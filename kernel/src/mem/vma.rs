@@ -0,0 +1,110 @@
+//! Virtual memory region tracking
+use abi::error::Error;
+use alloc::vec::Vec;
+
+use crate::mem::table::PageAttributes;
+
+/// Describes a contiguous range of pages reserved in an address space, which are only backed by
+/// physical memory once accessed
+#[derive(Clone, Copy)]
+pub struct VirtualMemoryRegion {
+    /// First address of the region
+    pub start: usize,
+    /// Number of pages in the region
+    pub page_count: usize,
+    /// Attributes the pages of this region are mapped with
+    pub attrs: PageAttributes,
+}
+
+/// Sorted list of non-overlapping virtual memory regions of an address space
+#[derive(Clone)]
+pub struct VirtualMemoryRegionList {
+    regions: Vec<VirtualMemoryRegion>,
+}
+
+impl VirtualMemoryRegion {
+    /// Returns the address immediately following the region
+    pub const fn end(&self) -> usize {
+        self.start + self.page_count * 0x1000
+    }
+
+    /// Returns `true` if the address belongs to the region
+    pub const fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end()
+    }
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.end()
+    }
+}
+
+impl VirtualMemoryRegionList {
+    /// Constructs an empty region list
+    pub const fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Returns the region the address belongs to, if any
+    pub fn find(&self, addr: usize) -> Option<&VirtualMemoryRegion> {
+        self.regions.iter().find(|region| region.contains(addr))
+    }
+
+    /// Returns `true` if no region overlaps the `start..end` range
+    pub fn is_free(&self, start: usize, end: usize) -> bool {
+        !self
+            .regions
+            .iter()
+            .any(|region| region.overlaps(start, end))
+    }
+
+    /// Inserts a new region into the list, returning an error if it overlaps an existing one
+    pub fn insert(&mut self, region: VirtualMemoryRegion) -> Result<(), Error> {
+        if !self.is_free(region.start, region.end()) {
+            return Err(Error::AlreadyExists);
+        }
+
+        let index = self
+            .regions
+            .iter()
+            .position(|r| r.start > region.start)
+            .unwrap_or(self.regions.len());
+        self.regions.insert(index, region);
+
+        Ok(())
+    }
+
+    /// Removes the `start..end` range from the list, splitting the regions it only partially
+    /// covers
+    pub fn remove(&mut self, start: usize, end: usize) {
+        let mut split = Vec::new();
+
+        self.regions.retain_mut(|region| {
+            if !region.overlaps(start, end) {
+                return true;
+            }
+
+            // Part after the removed range
+            if region.end() > end {
+                split.push(VirtualMemoryRegion {
+                    start: end,
+                    page_count: (region.end() - end) / 0x1000,
+                    attrs: region.attrs,
+                });
+            }
+
+            // Part before the removed range
+            if region.start < start {
+                region.page_count = (start - region.start) / 0x1000;
+                true
+            } else {
+                false
+            }
+        });
+
+        for region in split {
+            self.insert(region).unwrap();
+        }
+    }
+}
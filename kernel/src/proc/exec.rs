@@ -3,12 +3,13 @@ use core::mem::size_of;
 
 use abi::error::Error;
 use alloc::rc::Rc;
+use vfs::VnodeRef;
 
 use crate::{
     arch::aarch64::context::TaskContext,
     mem::{
         phys::{self, PageUsage},
-        table::{AddressSpace, PageAttributes},
+        table::{AddressSpace, PageAttributes, VirtualMemoryManager},
         ConvertAddress,
     },
     proc,
@@ -24,7 +25,7 @@ fn setup_args(space: &mut AddressSpace, virt: usize, args: &[&str]) -> Result<()
     let total_size = args_size + args_ptr_size;
 
     if total_size > 0x1000 {
-        todo!();
+        return Err(Error::InvalidArgument);
     }
 
     debugln!("arg data size = {}", args_size);
@@ -66,36 +67,52 @@ fn setup_args(space: &mut AddressSpace, virt: usize, args: &[&str]) -> Result<()
     Ok(())
 }
 
-/// Sets up a userspace structure from a slice defining an ELF binary
-pub fn create_from_memory(data: &[u8], args: &[&str]) -> Result<Rc<Process>, Error> {
+fn setup_binary(
+    space: &mut AddressSpace,
+    entry: usize,
+    args: &[&str],
+) -> Result<TaskContext, Error> {
     const USER_STACK_PAGES: usize = 8;
 
-    let mut space = AddressSpace::new_empty()?;
-    let elf_entry = proc::load_elf_from_memory(&mut space, data);
-
     let virt_stack_base = 0x10000000;
     // 0x1000 of guard page
     let virt_args_base = virt_stack_base + (USER_STACK_PAGES + 1) * 0x1000;
 
-    for i in 0..USER_STACK_PAGES {
-        let phys = phys::alloc_page(PageUsage::Used)?;
-        space.map_page(
-            virt_stack_base + i * 0x1000,
-            phys,
-            PageAttributes::AP_BOTH_READWRITE,
-        )?;
-    }
+    // Stack pages are only allocated once touched
+    space.allocate(
+        Some(virt_stack_base),
+        USER_STACK_PAGES,
+        PageAttributes::AP_BOTH_READWRITE,
+    )?;
 
-    setup_args(&mut space, virt_args_base, args)?;
+    setup_args(space, virt_args_base, args)?;
 
-    debugln!("Entry: {:#x}", elf_entry);
+    debugln!("Entry: {:#x}", entry);
 
-    let context = TaskContext::user(
-        elf_entry,
+    TaskContext::user(
+        entry,
         virt_args_base,
         space.physical_address(),
         virt_stack_base + USER_STACK_PAGES * 0x1000,
-    )?;
+    )
+}
+
+/// Sets up a userspace structure from an ELF binary file. The process still has to be registered
+/// with [Process::register] before it can be run.
+pub fn create_from_file(file: &VnodeRef, args: &[&str]) -> Result<Rc<Process>, Error> {
+    let mut space = AddressSpace::new_empty()?;
+
+    let context = match proc::load_elf_from_file(&mut space, file)
+        .and_then(|entry| setup_binary(&mut space, entry, args))
+    {
+        Ok(context) => context,
+        Err(e) => {
+            unsafe {
+                space.release();
+            }
+            return Err(e);
+        }
+    };
 
-    Ok(Process::new_with_context(Some(space), context))
+    Ok(Process::new_unregistered(Some(space), context))
 }
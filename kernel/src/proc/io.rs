@@ -1,13 +1,25 @@
 //! Process I/O management
-use abi::{error::Error, io::RawFd};
+use abi::{
+    error::Error,
+    io::{FdFlags, RawFd},
+};
 use alloc::collections::BTreeMap;
 use vfs::{FileRef, IoContext};
 
+/// Default limit on the number of file descriptors a process can have
+pub const DEFAULT_FD_LIMIT: usize = 64;
+
+struct FileDescriptor {
+    file: FileRef,
+    flags: FdFlags,
+}
+
 /// I/O context of a process, contains information like root, current directory and file
 /// descriptor table
 pub struct ProcessIo {
     ioctx: Option<IoContext>,
-    files: BTreeMap<RawFd, FileRef>,
+    files: BTreeMap<RawFd, FileDescriptor>,
+    fd_limit: usize,
 }
 
 impl ProcessIo {
@@ -16,6 +28,7 @@ impl ProcessIo {
         Self {
             ioctx: None,
             files: BTreeMap::new(),
+            fd_limit: DEFAULT_FD_LIMIT,
         }
     }
 
@@ -23,8 +36,42 @@ impl ProcessIo {
     pub fn file(&self, fd: RawFd) -> Result<FileRef, Error> {
         self.files
             .get(&fd)
-            .cloned()
-            .ok_or_else(|| Error::InvalidFile)
+            .map(|entry| entry.file.clone())
+            .ok_or(Error::BadDescriptor)
+    }
+
+    /// Returns an iterator over the descriptors present in the table along with the files they
+    /// refer to and their flags
+    pub fn files(&self) -> impl Iterator<Item = (RawFd, &FileRef, FdFlags)> {
+        self.files
+            .iter()
+            .map(|(&fd, entry)| (fd, &entry.file, entry.flags))
+    }
+
+    /// Returns the flags of a descriptor
+    pub fn file_flags(&self, fd: RawFd) -> Result<FdFlags, Error> {
+        self.files
+            .get(&fd)
+            .map(|entry| entry.flags)
+            .ok_or(Error::BadDescriptor)
+    }
+
+    /// Replaces the flags of a descriptor
+    pub fn set_file_flags(&mut self, fd: RawFd, flags: FdFlags) -> Result<(), Error> {
+        let entry = self.files.get_mut(&fd).ok_or(Error::BadDescriptor)?;
+        entry.flags = flags;
+        Ok(())
+    }
+
+    /// Returns the maximum number of descriptors the table can hold
+    pub fn fd_limit(&self) -> usize {
+        self.fd_limit
+    }
+
+    /// Changes the maximum number of descriptors the table can hold. Descriptors already placed
+    /// above the new limit stay open.
+    pub fn set_fd_limit(&mut self, limit: usize) {
+        self.fd_limit = limit;
     }
 
     /// Sets the inner I/O context
@@ -35,35 +82,77 @@ impl ProcessIo {
     /// Inserts a file into the descriptor table. Returns error if the file is already present for
     /// given descriptor.
     pub fn set_file(&mut self, fd: RawFd, file: FileRef) -> Result<(), Error> {
+        if fd.0 as usize >= self.fd_limit {
+            return Err(Error::BadDescriptor);
+        }
         if self.files.contains_key(&fd) {
-            todo!();
+            return Err(Error::AlreadyExists);
         }
 
-        self.files.insert(fd, file);
+        self.files.insert(
+            fd,
+            FileDescriptor {
+                file,
+                flags: FdFlags::new(),
+            },
+        );
         Ok(())
     }
 
     /// Allocates a slot for a file and returns it
     pub fn place_file(&mut self, file: FileRef) -> Result<RawFd, Error> {
-        for idx in 0..64 {
+        for idx in 0..self.fd_limit as u32 {
             let fd = RawFd(idx);
             if !self.files.contains_key(&fd) {
-                self.files.insert(fd, file);
+                self.set_file(fd, file)?;
                 return Ok(fd);
             }
         }
-        todo!();
+
+        Err(Error::TooManyFiles)
+    }
+
+    /// Makes another descriptor refer to the same file as `fd`. The duplicate is placed at
+    /// `target`, closing whatever it referred to before, or at the lowest free slot if no target
+    /// is given. Flags of the original descriptor are not copied.
+    pub fn duplicate_file(&mut self, fd: RawFd, target: Option<RawFd>) -> Result<RawFd, Error> {
+        let file = self.file(fd)?;
+
+        let Some(target) = target else {
+            return self.place_file(file);
+        };
+
+        if target.0 as usize >= self.fd_limit {
+            return Err(Error::BadDescriptor);
+        }
+        if target == fd {
+            return Ok(fd);
+        }
+
+        // Whatever the target referred to gets closed
+        self.files.insert(
+            target,
+            FileDescriptor {
+                file,
+                flags: FdFlags::new(),
+            },
+        );
+
+        Ok(target)
     }
 
     /// Closes the file and removes it from the table
     pub fn close_file(&mut self, fd: RawFd) -> Result<(), Error> {
-        let file = self.files.remove(&fd);
-        if file.is_none() {
-            todo!();
-        }
+        self.files.remove(&fd).ok_or(Error::BadDescriptor)?;
         Ok(())
     }
 
+    /// Closes all the files and releases the I/O context of a terminated process
+    pub fn handle_exit(&mut self) {
+        self.files.clear();
+        self.ioctx = None;
+    }
+
     /// Returns the inner I/O context reference
     pub fn ioctx(&mut self) -> &mut IoContext {
         self.ioctx.as_mut().unwrap()
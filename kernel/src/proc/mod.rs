@@ -2,26 +2,45 @@
 
 pub mod exec;
 pub mod io;
+pub mod pipe;
 pub mod wait;
 
-use aarch64_cpu::registers::TTBR0_EL1;
+use abi::error::Error;
+use alloc::vec;
 use elf::{
-    abi::{PF_W, PF_X, PT_LOAD},
+    abi::{EI_NIDENT, PF_W, PF_X, PT_LOAD},
     endian::AnyEndian,
-    ElfBytes,
+    file::{parse_ident, Class, FileHeader, ELF64_EHDR_TAILSIZE},
+    segment::{ProgramHeader, SegmentTable},
 };
-use tock_registers::interfaces::Writeable;
-
-use crate::{
-    arch::aarch64::table::tlb_flush_vaae1,
-    mem::{
-        phys::{self, PageUsage},
-        table::{AddressSpace, PageAttributes},
-    },
+use vfs::VnodeRef;
+
+use crate::mem::{
+    phys::{self, PageUsage},
+    table::{AddressSpace, PageAttributes, USER_VIRT_LIMIT},
+    ConvertAddress,
 };
 
-fn load_segment(space: &mut AddressSpace, addr: usize, data: &[u8], memsz: usize, elf_attrs: u32) {
-    let attrs = match (elf_attrs & PF_W, elf_attrs & PF_X) {
+fn load_segment<F: Fn(usize, &mut [u8]) -> Result<(), Error>>(
+    space: &mut AddressSpace,
+    phdr: &ProgramHeader,
+    read: F,
+) -> Result<(), Error> {
+    let addr = phdr.p_vaddr as usize;
+    let filesz = phdr.p_filesz as usize;
+    let memsz = phdr.p_memsz as usize;
+
+    // The segment must lie entirely within the user part of the address space
+    if filesz > memsz {
+        return Err(Error::InvalidFile);
+    }
+    let aligned_end = addr
+        .checked_add(memsz)
+        .filter(|&end| end <= USER_VIRT_LIMIT)
+        .ok_or(Error::InvalidFile)?
+        .next_multiple_of(0x1000);
+
+    let attrs = match (phdr.p_flags & PF_W, phdr.p_flags & PF_X) {
         (0, 0) => PageAttributes::AP_BOTH_READONLY,
         (_, 0) => PageAttributes::AP_BOTH_READWRITE,
         (0, _) => PageAttributes::AP_BOTH_READONLY,
@@ -29,63 +48,100 @@ fn load_segment(space: &mut AddressSpace, addr: usize, data: &[u8], memsz: usize
     };
 
     let aligned_start = addr & !0xFFF;
-    let aligned_end = (addr + memsz + 0xFFF) & !0xFFF;
 
-    // Map and write pages
+    // Map the pages not yet present in the address space
     for page in (aligned_start..aligned_end).step_by(0x1000) {
-        if let Some(_phys) = space.translate(page) {
-            todo!();
-        } else {
-            let phys = phys::alloc_page(PageUsage::Used).unwrap();
-            space
-                .map_page(page, phys, PageAttributes::AP_BOTH_READWRITE)
-                .unwrap();
+        if let Some(phys) = space.translate(page) {
+            // Page is shared with a previous segment, make sure it stays writable if either of
+            // the segments requires so
+            if phdr.p_flags & PF_W != 0 {
+                space.map_page(page, phys, PageAttributes::AP_BOTH_READWRITE)?;
+            }
+            continue;
+        }
 
-            debugln!("MAP (alloc) {:#x} -> {:#x}", page, phys);
-            tlb_flush_vaae1(page);
+        let phys = phys::alloc_page(PageUsage::Used)?;
+        unsafe {
+            core::slice::from_raw_parts_mut(phys.virtualize() as *mut u8, 0x1000).fill(0);
         }
+        space.map_page(page, phys, attrs)?;
+
+        debugln!("MAP (alloc) {:#x} -> {:#x}", page, phys);
     }
 
-    unsafe {
-        // Write the data
-        let dst = core::slice::from_raw_parts_mut(addr as *mut u8, memsz);
-        dst[..data.len()].copy_from_slice(data);
+    // Write the data through the kernel's mapping of the pages and zero the rest
+    let mut offset = 0;
+    while offset < memsz {
+        let virt = addr + offset;
+        let page_offset = virt & 0xFFF;
+        let count = core::cmp::min(memsz - offset, 0x1000 - page_offset);
+
+        let phys = space.translate(virt & !0xFFF).unwrap();
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut((phys.virtualize() + page_offset) as *mut u8, count)
+        };
+
+        if offset < filesz {
+            let file_count = core::cmp::min(count, filesz - offset);
+            read(offset, &mut dst[..file_count])?;
+            dst[file_count..].fill(0);
+        } else {
+            dst.fill(0);
+        }
 
-        // Zero the rest
-        dst[data.len()..memsz].fill(0);
+        offset += count;
     }
 
-    // Map the region as readonly
-    for page in (aligned_start..aligned_end).step_by(0x1000) {
-        let phys = space.translate(page).unwrap();
-        space.map_page(page, phys, attrs).unwrap();
+    Ok(())
+}
+
+fn read_exact(file: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<(), Error> {
+    let mut offset = 0;
+
+    while offset < buf.len() {
+        let count = file.read(pos + offset, &mut buf[offset..])?;
+        if count == 0 {
+            // Unexpected end of file
+            return Err(Error::InvalidFile);
+        }
+        offset += count;
     }
+
+    Ok(())
 }
 
-/// Loads an ELF image into the address space from a slice
-pub fn load_elf_from_memory(space: &mut AddressSpace, src: &[u8]) -> usize {
-    // Map the address space temporarily
-    TTBR0_EL1.set(space.physical_address() as u64);
+/// Loads an ELF image into the address space from a file
+pub fn load_elf_from_file(space: &mut AddressSpace, file: &VnodeRef) -> Result<usize, Error> {
+    if file.is_directory() {
+        return Err(Error::IsADirectory);
+    }
+
+    // Only the file header and program headers are read, the rest is loaded directly into the
+    // segment pages
+    let mut ehdr_buf = [0; EI_NIDENT + ELF64_EHDR_TAILSIZE];
+    read_exact(file, 0, &mut ehdr_buf)?;
 
-    let elf = ElfBytes::<AnyEndian>::minimal_parse(src).unwrap();
+    let ident = parse_ident::<AnyEndian>(&ehdr_buf[..EI_NIDENT]).map_err(|_| Error::InvalidFile)?;
+    if ident.1 != Class::ELF64 {
+        return Err(Error::InvalidFile);
+    }
+    let ehdr =
+        FileHeader::parse_tail(ident, &ehdr_buf[EI_NIDENT..]).map_err(|_| Error::InvalidFile)?;
 
-    for phdr in elf.segments().unwrap() {
+    let mut phdrs_buf = vec![0; ehdr.e_phentsize as usize * ehdr.e_phnum as usize];
+    read_exact(file, ehdr.e_phoff as usize, &mut phdrs_buf)?;
+
+    for phdr in SegmentTable::new(ehdr.endianness, ehdr.class, &phdrs_buf) {
         if phdr.p_type != PT_LOAD {
             continue;
         }
 
         debugln!("LOAD {:#x}", phdr.p_vaddr);
-        let data = &src[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize];
-        load_segment(
-            space,
-            phdr.p_vaddr as usize,
-            data,
-            phdr.p_memsz as usize,
-            phdr.p_flags,
-        );
+        let data_offset = phdr.p_offset as usize;
+        load_segment(space, &phdr, |offset, dst| {
+            read_exact(file, data_offset + offset, dst)
+        })?;
     }
 
-    TTBR0_EL1.set_baddr(0);
-
-    elf.ehdr.e_entry as usize
+    Ok(ehdr.e_entry as usize)
 }
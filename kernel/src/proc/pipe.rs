@@ -0,0 +1,292 @@
+//! Anonymous pipes
+use abi::{
+    error::Error,
+    io::{FileMetadata, OpenFlags},
+};
+use alloc::{boxed::Box, sync::Arc, vec};
+use vfs::{FileRef, Stream, StreamRef, Vnode, VnodeImpl, VnodeKind, VnodeRef};
+
+use crate::{
+    proc::wait::{self, Wait},
+    sync::IrqSafeSpinlock,
+};
+
+const PIPE_CAPACITY: usize = 4096;
+
+struct PipeInner {
+    data: Box<[u8]>,
+    rd: usize,
+    len: usize,
+    reader_closed: bool,
+    writer_closed: bool,
+}
+
+/// Ring buffer shared by both ends of a pipe
+struct Pipe {
+    wait_read: Wait,
+    wait_write: Wait,
+    inner: IrqSafeSpinlock<PipeInner>,
+}
+
+/// Read end of a pipe. The end is closed once nothing refers to it anymore.
+struct PipeReader {
+    pipe: Arc<Pipe>,
+}
+
+/// Write end of a pipe
+struct PipeWriter {
+    pipe: Arc<Pipe>,
+}
+
+/// Node data of either end of a pipe
+struct PipeNode {
+    pipe: Arc<Pipe>,
+    end: StreamRef,
+    write: bool,
+}
+
+impl PipeInner {
+    fn read(&mut self, data: &mut [u8]) -> usize {
+        let count = core::cmp::min(data.len(), self.len);
+
+        for byte in data[..count].iter_mut() {
+            *byte = self.data[self.rd];
+            self.rd = (self.rd + 1) % PIPE_CAPACITY;
+        }
+        self.len -= count;
+
+        count
+    }
+
+    fn write(&mut self, data: &[u8]) -> usize {
+        let count = core::cmp::min(data.len(), PIPE_CAPACITY - self.len);
+
+        for &byte in data[..count].iter() {
+            self.data[(self.rd + self.len) % PIPE_CAPACITY] = byte;
+            self.len += 1;
+        }
+
+        count
+    }
+}
+
+impl Pipe {
+    fn new() -> Self {
+        Self {
+            wait_read: Wait::new("pipe_read"),
+            wait_write: Wait::new("pipe_write"),
+            inner: IrqSafeSpinlock::new(PipeInner {
+                data: vec![0; PIPE_CAPACITY].into_boxed_slice(),
+                rd: 0,
+                len: 0,
+                reader_closed: false,
+                writer_closed: false,
+            }),
+        }
+    }
+
+    // Blocks until there's some data in the pipe. Returns zero once the pipe is drained and the
+    // writer is gone.
+    fn read(&self, data: &mut [u8], blocking: bool) -> Result<usize, Error> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let mut inner = self.inner.lock();
+        while inner.len == 0 {
+            if inner.writer_closed {
+                return Ok(0);
+            }
+            if !blocking {
+                return Err(Error::WouldBlock);
+            }
+
+            // Subscribe while the ring is still locked, so a write or the writer going away
+            // can't slip in before the wait
+            wait::begin_wait();
+            self.wait_read.subscribe();
+            drop(inner);
+            wait::wait_any(None)?;
+            inner = self.inner.lock();
+        }
+
+        let count = inner.read(data);
+        drop(inner);
+        self.wait_write.wakeup_all();
+
+        Ok(count)
+    }
+
+    // Returns whether given end can be used without blocking, otherwise subscribes the pending
+    // wait of the current task to the pipe
+    fn poll(&self, write: bool) -> bool {
+        let inner = self.inner.lock();
+
+        if write {
+            if inner.reader_closed || inner.len < PIPE_CAPACITY {
+                return true;
+            }
+            self.wait_write.subscribe();
+        } else {
+            if inner.writer_closed || inner.len != 0 {
+                return true;
+            }
+            self.wait_read.subscribe();
+        }
+
+        false
+    }
+
+    // Blocks until all the data is placed into the pipe or the reader goes away. Non-blocking
+    // writes only place what fits.
+    fn write(&self, data: &[u8], blocking: bool) -> Result<usize, Error> {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let mut inner = self.inner.lock();
+
+            if inner.reader_closed {
+                // Report what was already written, the error will come with the next call
+                return if offset == 0 {
+                    Err(Error::BrokenPipe)
+                } else {
+                    Ok(offset)
+                };
+            }
+
+            let count = inner.write(&data[offset..]);
+
+            if count == 0 {
+                if !blocking {
+                    return if offset == 0 {
+                        Err(Error::WouldBlock)
+                    } else {
+                        Ok(offset)
+                    };
+                }
+                // Same as with reads, subscribe before the ring is unlocked
+                wait::begin_wait();
+                self.wait_write.subscribe();
+                drop(inner);
+                wait::wait_any(None)?;
+            } else {
+                drop(inner);
+                offset += count;
+                self.wait_read.wakeup_all();
+            }
+        }
+
+        Ok(offset)
+    }
+}
+
+impl Stream for PipeReader {
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Error> {
+        self.pipe.read(data, blocking)
+    }
+
+    fn write(&self, _blocking: bool, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::InvalidArgument)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Error> {
+        if write {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(self.pipe.poll(false))
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.inner.lock().reader_closed = true;
+        self.pipe.wait_write.wakeup_all();
+    }
+}
+
+impl Stream for PipeWriter {
+    fn read(&self, _blocking: bool, _data: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::InvalidArgument)
+    }
+
+    fn write(&self, blocking: bool, data: &[u8]) -> Result<usize, Error> {
+        self.pipe.write(data, blocking)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Error> {
+        if !write {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(self.pipe.poll(true))
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.inner.lock().writer_closed = true;
+        self.pipe.wait_read.wakeup_all();
+    }
+}
+
+impl VnodeImpl for PipeNode {
+    fn create(&mut self, _at: &VnodeRef, _name: &str, _kind: VnodeKind) -> Result<VnodeRef, Error> {
+        Err(Error::NotADirectory)
+    }
+
+    fn open(&mut self, _node: &VnodeRef, opts: OpenFlags) -> Result<usize, Error> {
+        let wrong_end = if self.write {
+            opts.is_read()
+        } else {
+            opts.is_write()
+        };
+        if wrong_end {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: &VnodeRef, _pos: usize, data: &mut [u8]) -> Result<usize, Error> {
+        self.end.read(true, data)
+    }
+
+    fn write(&mut self, _node: &VnodeRef, _pos: usize, data: &[u8]) -> Result<usize, Error> {
+        self.end.write(true, data)
+    }
+
+    fn stream(&mut self) -> Option<StreamRef> {
+        Some(self.end.clone())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.size = self.pipe.inner.lock().len as u64;
+        Ok(())
+    }
+}
+
+/// Creates an anonymous pipe, returning its read and write ends
+pub fn create() -> Result<(FileRef, FileRef), Error> {
+    let pipe = Arc::new(Pipe::new());
+
+    let read_node = Vnode::new("", VnodeKind::Pipe);
+    read_node.set_data(Box::new(PipeNode {
+        pipe: pipe.clone(),
+        end: Arc::new(PipeReader { pipe: pipe.clone() }),
+        write: false,
+    }));
+
+    let write_node = Vnode::new("", VnodeKind::Pipe);
+    write_node.set_data(Box::new(PipeNode {
+        pipe: pipe.clone(),
+        end: Arc::new(PipeWriter { pipe }),
+        write: true,
+    }));
+
+    let read = read_node.open(OpenFlags::new().read())?;
+    let write = write_node.open(OpenFlags::new().write())?;
+
+    Ok((read, write))
+}
@@ -2,7 +2,10 @@
 use core::time::Duration;
 
 use abi::error::Error;
-use alloc::{collections::LinkedList, rc::Rc};
+use alloc::{
+    collections::LinkedList,
+    rc::{Rc, Weak},
+};
 
 use crate::{
     arch::PLATFORM, device::platform::Platform, sync::IrqSafeSpinlock, task::process::Process,
@@ -17,18 +20,29 @@ pub enum WaitStatus {
     Pending,
     /// Channel has data available
     Done,
+    /// Deadline of the wait was reached before any channel signalled availability
+    TimedOut,
 }
 
 /// Wait notification channel
 pub struct Wait {
-    queue: IrqSafeSpinlock<LinkedList<Rc<Process>>>,
+    queue: IrqSafeSpinlock<LinkedList<Waiter>>,
     // Used for tracing waits
     #[allow(dead_code)]
     name: &'static str,
 }
 
+// Tasks are queued along with the token of the wait they've subscribed with, so entries left
+// behind by waits which already ended in some other way can be told apart and skipped. The
+// references are weak, so such leftovers don't keep exited tasks from being dropped.
+struct Waiter {
+    process: Weak<Process>,
+    token: u64,
+}
+
 struct Timeout {
-    process: Rc<Process>,
+    process: Weak<Process>,
+    token: u64,
     deadline: Duration,
 }
 
@@ -45,28 +59,30 @@ impl Wait {
     pub fn wakeup_some(&self, mut limit: usize) -> usize {
         let mut queue = self.queue.lock();
         let mut count = 0;
-        while limit != 0 && !queue.is_empty() {
-            let proc = queue.pop_front().unwrap();
+        while limit != 0 {
+            let Some(waiter) = queue.pop_front() else {
+                break;
+            };
+            let Some(process) = waiter.process.upgrade() else {
+                continue;
+            };
+
+            if !process.complete_wait(waiter.token, WaitStatus::Done) {
+                continue;
+            }
 
             {
                 let mut tick_lock = TICK_LIST.lock();
                 let mut cursor = tick_lock.cursor_front_mut();
 
                 while let Some(item) = cursor.current() {
-                    if proc.id() == item.process.id() {
+                    if Weak::ptr_eq(&waiter.process, &item.process) && waiter.token == item.token {
                         cursor.remove_current();
                         break;
                     } else {
                         cursor.move_next();
                     }
                 }
-
-                drop(tick_lock);
-
-                unsafe {
-                    proc.set_wait_status(WaitStatus::Done);
-                }
-                proc.enqueue_somewhere();
             }
 
             limit -= 1;
@@ -86,63 +102,83 @@ impl Wait {
         self.wakeup_some(1);
     }
 
-    /// Suspends the task until either the deadline is reached or this channel signals availability
-    pub fn wait(&'static self, deadline: Option<Duration>) -> Result<(), Error> {
+    /// Makes the pending wait of the current task (see [begin_wait]) also finish once this
+    /// channel signals availability
+    pub fn subscribe(&self) {
         let process = Process::current();
-        let mut queue_lock = self.queue.lock();
-        queue_lock.push_back(process.clone());
-        unsafe {
-            process.setup_wait(self);
-        }
+        let token = process.wait_token();
+        let mut queue = self.queue.lock();
 
-        if let Some(deadline) = deadline {
-            TICK_LIST.lock().push_back(Timeout {
-                process: process.clone(),
-                deadline,
-            });
+        // Any entries of the task which are still there belong to its earlier waits
+        let mut cursor = queue.cursor_front_mut();
+        while let Some(item) = cursor.current() {
+            if item.process.strong_count() == 0
+                || Weak::as_ptr(&item.process) == Rc::as_ptr(&process)
+            {
+                cursor.remove_current();
+            } else {
+                cursor.move_next();
+            }
         }
 
-        loop {
-            match process.wait_status() {
-                WaitStatus::Pending => (),
-                WaitStatus::Done => return Ok(()),
-                WaitStatus::Interrupted => todo!(),
-            }
+        queue.push_back(Waiter {
+            process: Rc::downgrade(&process),
+            token,
+        });
+    }
 
-            drop(queue_lock);
-            process.suspend();
+    /// Suspends the task until either the deadline is reached or this channel signals availability
+    pub fn wait(&self, deadline: Option<Duration>) -> Result<(), Error> {
+        begin_wait();
+        self.subscribe();
+        wait_any(deadline)
+    }
+}
 
-            queue_lock = self.queue.lock();
+static TICK_LIST: IrqSafeSpinlock<LinkedList<Timeout>> = IrqSafeSpinlock::new(LinkedList::new());
 
-            if let Some(deadline) = deadline {
-                let now = PLATFORM.timestamp_source().timestamp()?;
+/// Sets up a pending wait for the current task, which can then be subscribed to any number of
+/// channels with [Wait::subscribe]. The wait must then be either performed with [wait_any] or
+/// abandoned with [cancel_wait].
+pub fn begin_wait() {
+    let process = Process::current();
+    unsafe {
+        process.setup_wait();
+    }
+}
 
-                if now > deadline {
-                    let mut cursor = queue_lock.cursor_front_mut();
+/// Abandons the pending wait of the current task, so the channels it was subscribed to no longer
+/// affect it
+pub fn cancel_wait() {
+    let process = Process::current();
+    process.complete_wait(process.wait_token(), WaitStatus::Done);
+}
 
-                    while let Some(item) = cursor.current() {
-                        if item.id() == process.id() {
-                            cursor.remove_current();
-                            return Err(Error::TimedOut);
-                        } else {
-                            cursor.move_next();
-                        }
-                    }
+/// Suspends the current task until either the deadline is reached or any of the channels its
+/// pending wait was subscribed to signals availability
+pub fn wait_any(deadline: Option<Duration>) -> Result<(), Error> {
+    let process = Process::current();
+
+    if let Some(deadline) = deadline {
+        TICK_LIST.lock().push_back(Timeout {
+            process: Rc::downgrade(&process),
+            token: process.wait_token(),
+            deadline,
+        });
+    }
 
-                    panic!();
-                }
-            }
-        }
+    match process.suspend_wait() {
+        WaitStatus::Done => Ok(()),
+        WaitStatus::TimedOut => Err(Error::TimedOut),
+        WaitStatus::Pending | WaitStatus::Interrupted => todo!(),
     }
 }
 
-static TICK_LIST: IrqSafeSpinlock<LinkedList<Timeout>> = IrqSafeSpinlock::new(LinkedList::new());
-
 /// Suspends current task until given deadline
 pub fn sleep(timeout: Duration, remaining: &mut Duration) -> Result<(), Error> {
     static SLEEP_NOTIFY: Wait = Wait::new("sleep");
     let now = PLATFORM.timestamp_source().timestamp()?;
-    let deadline = now + timeout;
+    let deadline = now.checked_add(timeout).ok_or(Error::InvalidArgument)?;
 
     match SLEEP_NOTIFY.wait(Some(deadline)) {
         // Just what we expected
@@ -165,8 +201,11 @@ pub fn tick() {
     while let Some(item) = cursor.current() {
         if now > item.deadline {
             let t = cursor.remove_current().unwrap();
+            let Some(process) = t.process.upgrade() else {
+                continue;
+            };
 
-            t.process.enqueue_somewhere();
+            process.complete_wait(t.token, WaitStatus::TimedOut);
         } else {
             cursor.move_next();
         }
@@ -144,6 +144,55 @@ impl Drop for IrqGuard {
     }
 }
 
+// Raw lock of the vfs structures, which masks IRQs the same way [IrqSafeSpinlock] does. The
+// state keeps the IRQ mask to restore on release, with the lowest bit (RES0 in DAIF) marking the
+// lock as taken.
+const VFS_LOCK_TAKEN: usize = 1;
+
+#[no_mangle]
+fn __vfs_raw_lock(state: &AtomicUsize) {
+    let daif = DAIF.get() as usize;
+    DAIF.modify(DAIF::I::SET);
+
+    while state
+        .compare_exchange(
+            0,
+            daif | VFS_LOCK_TAKEN,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+}
+
+#[no_mangle]
+fn __vfs_raw_try_lock(state: &AtomicUsize) -> bool {
+    let daif = DAIF.get() as usize;
+    DAIF.modify(DAIF::I::SET);
+
+    let taken = state
+        .compare_exchange(
+            0,
+            daif | VFS_LOCK_TAKEN,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        )
+        .is_ok();
+    if !taken {
+        DAIF.set(daif as u64);
+    }
+
+    taken
+}
+
+#[no_mangle]
+fn __vfs_raw_unlock(state: &AtomicUsize) {
+    let daif = state.swap(0, Ordering::Release) & !VFS_LOCK_TAKEN;
+    DAIF.set(daif as u64);
+}
+
 // SpinFence impls
 impl SpinFence {
     /// Constructs a new [SpinFence]
@@ -1,67 +1,316 @@
 //! System function call handlers
-use core::time::Duration;
+use core::{
+    mem::{size_of, MaybeUninit},
+    time::Duration,
+};
 
 use abi::{
     error::{Error, IntoSyscallResult},
-    io::{OpenFlags, RawFd},
+    io::{
+        DirectoryEntry, FdFlags, FileControl, FileMetadata, FileType, MountOptions, OpenFlags,
+        PollEvents, PollFd, RawFd, RawStr, SeekFrom,
+    },
+    process::{RawSpawnOption, SpawnOption, SpawnOptions},
     SyscallFunction,
 };
-use vfs::{Read, Write};
+use alloc::{string::String, vec, vec::Vec};
+use vfs::{File, FileRef};
 
 use crate::{
+    arch::{
+        aarch64::{
+            context::TaskContext,
+            exception::ExceptionFrame,
+            usercopy::{copy_from_user, copy_to_user},
+        },
+        PLATFORM,
+    },
+    device::platform::Platform,
+    fs,
     mem::table::{PageAttributes, VirtualMemoryManager},
-    proc::wait,
-    task::process::Process,
+    proc::{self, io::ProcessIo, pipe, wait},
+    task::{process::Process, ProcessId},
 };
 
-fn arg_buffer_ref<'a>(base: usize, len: usize) -> Result<&'a [u8], Error> {
-    if base + len > crate::mem::KERNEL_VIRT_OFFSET {
-        panic!("Invalid argument");
+// Upper bound on the amount of data a single read or write call moves through a kernel buffer.
+// Larger requests are cut short, which callers see as a partial transfer.
+const MAX_TRANSFER_SIZE: usize = 0x10000;
+// Upper bound on the length of paths and other strings passed to the kernel
+const MAX_STRING_LEN: usize = 0x1000;
+
+fn arg_user_str(base: usize, len: usize) -> Result<String, Error> {
+    if len > MAX_STRING_LEN {
+        return Err(Error::InvalidArgument);
+    }
+    let mut bytes = vec![0; len];
+    copy_from_user(&mut bytes, base)?;
+    String::from_utf8(bytes).map_err(|_| Error::InvalidArgument)
+}
+
+fn arg_user_value<T: Copy>(base: usize) -> Result<T, Error> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes =
+        unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    copy_from_user(bytes, base)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+fn write_user_value<T: Copy>(base: usize, value: &T) -> Result<(), Error> {
+    let bytes =
+        unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    copy_to_user(base, bytes)
+}
+
+fn arg_user_vec<T: Copy>(base: usize, count: usize) -> Result<Vec<T>, Error> {
+    let size = count
+        .checked_mul(size_of::<T>())
+        .ok_or(Error::InvalidArgument)?;
+    let mut items = Vec::<T>::new();
+    items
+        .try_reserve_exact(count)
+        .map_err(|_| Error::OutOfMemory)?;
+    let bytes = unsafe { core::slice::from_raw_parts_mut(items.as_mut_ptr() as *mut u8, size) };
+    copy_from_user(bytes, base)?;
+    unsafe {
+        items.set_len(count);
+    }
+    Ok(items)
+}
+
+fn write_user_slice<T: Copy>(base: usize, items: &[T]) -> Result<(), Error> {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(items.as_ptr() as *const u8, core::mem::size_of_val(items))
+    };
+    copy_to_user(base, bytes)
+}
+
+fn spawn_io(io: &mut ProcessIo, child: &Process, optional: &[SpawnOption]) -> Result<(), Error> {
+    let mut child_io = child.io.lock();
+    let mut fd_limit = io.fd_limit();
+    child_io.set_ioctx(io.ioctx().clone());
+    child_io.set_fd_limit(fd_limit);
+
+    // Explicitly requested descriptors go first
+    for opt in optional {
+        match *opt {
+            SpawnOption::InheritFile { source, child } => {
+                let file = io.file(source)?;
+                child_io.set_file(child, file)?;
+            }
+            // The child can't get more descriptors than the caller has
+            SpawnOption::FdLimit(limit) => {
+                if limit == 0 || limit as usize > io.fd_limit() {
+                    return Err(Error::InvalidArgument);
+                }
+                fd_limit = limit as usize;
+            }
+        }
+    }
+
+    // The rest of the caller's descriptors are inherited as-is, unless they're marked to be
+    // closed when a new program is executed
+    for (fd, file, flags) in io.files() {
+        if !flags.is_close_on_exec() && child_io.file(fd).is_err() {
+            child_io.set_file(fd, file.clone())?;
+        }
+    }
+
+    // Applied last, as the limit may not be below any of the descriptors the child inherits
+    if let Some((fd, _, _)) = child_io.files().last() {
+        if fd.0 as usize >= fd_limit {
+            return Err(Error::InvalidArgument);
+        }
+    }
+    child_io.set_fd_limit(fd_limit);
+
+    Ok(())
+}
+
+fn spawn(options: &SpawnOptions) -> Result<ProcessId, Error> {
+    // The options struct itself comes from userspace, so everything it refers to has to be
+    // copied in and validated as well
+    let program = arg_user_str(options.program.ptr as usize, options.program.len)?;
+    let arguments = arg_user_vec::<RawStr>(options.arguments as usize, options.argument_count)?
+        .iter()
+        .map(|arg| arg_user_str(arg.ptr as usize, arg.len))
+        .collect::<Result<Vec<_>, _>>()?;
+    let arguments = arguments.iter().map(String::as_str).collect::<Vec<_>>();
+    let optional =
+        arg_user_vec::<RawSpawnOption>(options.optional as usize, options.optional_count)?
+            .into_iter()
+            .map(|opt| SpawnOption::from_raw(opt).ok_or(Error::InvalidArgument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+    let proc = Process::current();
+    let mut io = proc.io.lock();
+
+    let node = io.ioctx().find(None, &program, true)?;
+    let child = proc::exec::create_from_file(&node, &arguments)?;
+
+    // Only registered once fully set up, so a failure just drops it
+    if let Err(e) = spawn_io(&mut io, &child, &optional) {
+        unsafe {
+            child.address_space().release();
+        }
+        return Err(e);
+    }
+
+    let id = child.register();
+    proc.add_child(&child);
+
+    debugln!("spawn({:?}) = {}", program, id);
+    child.enqueue_somewhere();
+
+    Ok(id)
+}
+
+fn fork_io(proc: &Process, child: &Process) -> Result<(), Error> {
+    let mut io = proc.io.lock();
+    let mut child_io = child.io.lock();
+
+    child_io.set_ioctx(io.ioctx().clone());
+    child_io.set_fd_limit(io.fd_limit());
+    for (fd, file, flags) in io.files() {
+        child_io.set_file(fd, file.clone())?;
+        child_io.set_file_flags(fd, flags)?;
     }
-    Ok(unsafe { core::slice::from_raw_parts(base as *const u8, len) })
+
+    Ok(())
 }
 
-fn arg_buffer_mut<'a>(base: usize, len: usize) -> Result<&'a mut [u8], Error> {
-    if base + len > crate::mem::KERNEL_VIRT_OFFSET {
-        panic!("Invalid argument");
+fn fork(frame: &ExceptionFrame) -> Result<ProcessId, Error> {
+    let proc = Process::current();
+    let space = proc.address_space().fork()?;
+    let context = match TaskContext::user_fork(frame, space.physical_address()) {
+        Ok(context) => context,
+        Err(e) => {
+            unsafe {
+                space.release();
+            }
+            return Err(e);
+        }
+    };
+    // Only registered once fully set up, so a failure just drops it
+    let child = Process::new_unregistered(Some(space), context);
+
+    if let Err(e) = fork_io(&proc, &child) {
+        unsafe {
+            child.address_space().release();
+        }
+        return Err(e);
     }
-    Ok(unsafe { core::slice::from_raw_parts_mut(base as *mut u8, len) })
+
+    child.register();
+    proc.add_child(&child);
+
+    let id = child.id();
+    debugln!("fork() = {}", id);
+    child.enqueue_somewhere();
+
+    Ok(id)
 }
 
-fn arg_user_str<'a>(base: usize, len: usize) -> Result<&'a str, Error> {
-    let slice = arg_buffer_ref(base, len)?;
-    Ok(core::str::from_utf8(slice).unwrap())
+// Checks the readiness of the descriptors once, filling in their ready events. Descriptors which
+// aren't ready subscribe the pending wait of the task to their notifications, the ones which can't
+// be polled for the requested events are reported as ready with an error.
+fn poll_files(fds: &mut [PollFd], files: &[FileRef]) -> usize {
+    let mut count = 0;
+
+    for (entry, file) in fds.iter_mut().zip(files) {
+        let file_lock = file.lock();
+
+        entry.revents = PollEvents::empty();
+        for (event, write) in [(PollEvents::READ, false), (PollEvents::WRITE, true)] {
+            if !entry.events.contains(event) {
+                continue;
+            }
+            match file_lock.is_ready(write) {
+                Ok(true) => entry.revents = entry.revents.union(event),
+                Ok(false) => (),
+                Err(_) => entry.revents = entry.revents.union(PollEvents::ERROR),
+            }
+        }
+
+        if !entry.revents.is_empty() {
+            count += 1;
+        }
+    }
+
+    count
 }
 
-/// Entrypoint for system calls that takes raw argument values
-pub fn raw_syscall_handler(func: u64, args: &[u64]) -> u64 {
-    let Ok(func) = SyscallFunction::try_from(func as usize) else {
-        todo!("Undefined syscall: {}", func);
+fn poll(fds: &mut [PollFd], timeout: Option<Duration>) -> Result<usize, Error> {
+    let proc = Process::current();
+    let files = {
+        let io = proc.io.lock();
+        fds.iter()
+            .map(|entry| io.file(entry.fd))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let deadline = match timeout {
+        Some(timeout) => Some(
+            PLATFORM
+                .timestamp_source()
+                .timestamp()?
+                .checked_add(timeout)
+                .ok_or(Error::InvalidArgument)?,
+        ),
+        None => None,
     };
 
+    loop {
+        let expired = match deadline {
+            Some(deadline) => PLATFORM.timestamp_source().timestamp()? >= deadline,
+            None => false,
+        };
+
+        wait::begin_wait();
+        let count = poll_files(fds, &files);
+
+        if count != 0 || expired {
+            wait::cancel_wait();
+            return Ok(count);
+        }
+
+        match wait::wait_any(deadline) {
+            Ok(()) => (),
+            Err(Error::TimedOut) => return Ok(0),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn syscall_handler(
+    func: SyscallFunction,
+    args: &[u64],
+    frame: &ExceptionFrame,
+) -> Result<usize, Error> {
     match func {
         SyscallFunction::DebugTrace => {
             let pid = Process::get_current()
                 .as_deref()
                 .map(Process::id)
                 .unwrap_or(0);
-            let arg = arg_user_str(args[0] as usize, args[1] as usize).unwrap();
+            let arg = arg_user_str(args[0] as usize, args[1] as usize)?;
             debugln!("[{}] TRACE: {:?}", pid, arg);
-            0
-            // 0
+
+            Ok(0)
         }
         SyscallFunction::Nanosleep => {
             let seconds = args[0];
-            let nanos = args[1] as u32;
-            let duration = Duration::new(seconds, nanos);
+            let nanos = args[1];
+            if nanos >= 1_000_000_000 {
+                return Err(Error::InvalidArgument);
+            }
+            let duration = Duration::new(seconds, nanos as u32);
             let mut remaining = Duration::ZERO;
 
-            wait::sleep(duration, &mut remaining).unwrap();
+            wait::sleep(duration, &mut remaining)?;
 
-            0
+            Ok(0)
         }
         SyscallFunction::Exit => {
-            Process::current().exit(args[0] as _);
+            Process::current().exit(args[0] as i32);
             panic!();
         }
         SyscallFunction::MapMemory => {
@@ -71,13 +320,13 @@ pub fn raw_syscall_handler(func: u64, args: &[u64]) -> u64 {
             let space = proc.address_space();
 
             if len & 0xFFF != 0 {
-                todo!();
+                return Err(Error::InvalidArgument);
             }
 
             let addr = space.allocate(None, len / 0x1000, PageAttributes::AP_BOTH_READWRITE);
             debugln!("mmap({:#x}) = {:x?}", len, addr);
 
-            addr.into_syscall_result() as u64
+            addr
         }
         SyscallFunction::UnmapMemory => {
             let addr = args[0] as usize;
@@ -86,39 +335,56 @@ pub fn raw_syscall_handler(func: u64, args: &[u64]) -> u64 {
             let proc = Process::current();
             let space = proc.address_space();
 
-            if len & 0xFFF != 0 {
-                todo!();
+            if addr & 0xFFF != 0 || len & 0xFFF != 0 {
+                return Err(Error::InvalidArgument);
             }
 
-            let res = space.deallocate(addr, len);
             debugln!("munmap({:#x}, {:#x})", addr, len);
+            space.deallocate(addr, len)?;
 
-            res.into_syscall_result() as u64
+            Ok(0)
         }
         SyscallFunction::Write => {
             let fd = RawFd(args[0] as u32);
-            let data = arg_buffer_ref(args[1] as _, args[2] as _).unwrap();
+            let len = (args[2] as usize).min(MAX_TRANSFER_SIZE);
+            let mut data = vec![0; len];
+            copy_from_user(&mut data, args[1] as usize)?;
 
             let proc = Process::current();
-            let io = proc.io.lock();
-            let file = io.file(fd).unwrap();
-            let mut file_borrow = file.borrow_mut();
+            // Neither the descriptor table nor the file must stay locked if the operation blocks
+            let file = proc.io.lock().file(fd)?;
 
-            file_borrow.write(data).into_syscall_result() as u64
+            File::write_shared(&file, &data)
         }
         SyscallFunction::Read => {
             let fd = RawFd(args[0] as u32);
-            let data = arg_buffer_mut(args[1] as _, args[2] as _).unwrap();
+            let base = args[1] as usize;
+            let len = (args[2] as usize).min(MAX_TRANSFER_SIZE);
+
+            let proc = Process::current();
+            // Don't consume any data if it can't be returned
+            proc.address_space().validate_user_range(base, len, true)?;
+            let file = proc.io.lock().file(fd)?;
+
+            let mut data = vec![0; len];
+            let count = File::read_shared(&file, &mut data)?;
+            copy_to_user(base, &data[..count])?;
+
+            Ok(count)
+        }
+        SyscallFunction::Seek => {
+            let fd = RawFd(args[0] as u32);
+            let from = SeekFrom::from_raw(args[1] as usize, args[2] as usize)
+                .ok_or(Error::InvalidArgument)?;
 
             let proc = Process::current();
-            let io = proc.io.lock();
-            let file = io.file(fd).unwrap();
-            let mut file_borrow = file.borrow_mut();
+            let file = proc.io.lock().file(fd)?;
+            let mut file_lock = file.lock();
 
-            file_borrow.read(data).into_syscall_result() as u64
+            file_lock.seek(from)
         }
         SyscallFunction::Open => {
-            let path = arg_user_str(args[0] as usize, args[1] as usize).unwrap();
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
             let opts = OpenFlags(args[2] as u32);
 
             debugln!("sys_open {}", path);
@@ -126,17 +392,297 @@ pub fn raw_syscall_handler(func: u64, args: &[u64]) -> u64 {
             let proc = Process::current();
             let mut io = proc.io.lock();
 
-            let file = io.ioctx().open(None, path, opts).unwrap();
-            let fd = io.place_file(file);
+            let file = io.ioctx().open(None, &path, opts)?;
+            let fd = io.place_file(file)?;
+
+            if opts.is_close_on_exec() {
+                io.set_file_flags(fd, FdFlags::new().close_on_exec())?;
+            }
+
+            Ok(fd.0 as usize)
+        }
+        SyscallFunction::OpenDirectory => {
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
+
+            debugln!("sys_opendir {}", path);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            let file = io.ioctx().find(None, &path, true)?.open_directory()?;
+            let fd = io.place_file(file)?;
+
+            Ok(fd.0 as usize)
+        }
+        SyscallFunction::ReadDirectory => {
+            let fd = RawFd(args[0] as u32);
+            let base = args[1] as usize;
+            let count = (args[2] as usize).min(MAX_TRANSFER_SIZE / size_of::<DirectoryEntry>());
+            let mut entries = vec![
+                DirectoryEntry {
+                    id: 0,
+                    ty: FileType::File,
+                    name: [0; DirectoryEntry::NAME_MAX + 1],
+                };
+                count
+            ];
+
+            let proc = Process::current();
+            proc.address_space().validate_user_range(
+                base,
+                count * size_of::<DirectoryEntry>(),
+                true,
+            )?;
+            let file = proc.io.lock().file(fd)?;
+            let mut file_lock = file.lock();
+
+            let count = file_lock.read_dir(&mut entries)?;
+            write_user_slice(base, &entries[..count])?;
+
+            Ok(count)
+        }
+        SyscallFunction::GetMetadata => {
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
+            let follow = args[3] != 0;
+
+            let proc = Process::current();
+            let node = proc.io.lock().ioctx().find(None, &path, follow)?;
+
+            write_user_value::<FileMetadata>(args[2] as usize, &node.metadata()?)?;
 
-            fd.into_syscall_result() as u64
+            Ok(0)
+        }
+        SyscallFunction::FileMetadata => {
+            let fd = RawFd(args[0] as u32);
+
+            let proc = Process::current();
+            let file = proc.io.lock().file(fd)?;
+            let metadata = file.lock().metadata()?;
+
+            write_user_value::<FileMetadata>(args[1] as usize, &metadata)?;
+
+            Ok(0)
+        }
+        SyscallFunction::CreateDirectory => {
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
+
+            debugln!("mkdir({:?})", path);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            io.ioctx().create_directory(None, &path)?;
+
+            Ok(0)
+        }
+        SyscallFunction::Remove => {
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
+            let directory = args[2] != 0;
+
+            debugln!("remove({:?}, directory={})", path, directory);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            io.ioctx().remove(None, &path, directory)?;
+
+            Ok(0)
+        }
+        SyscallFunction::Rename => {
+            let old_path = arg_user_str(args[0] as usize, args[1] as usize)?;
+            let new_path = arg_user_str(args[2] as usize, args[3] as usize)?;
+
+            debugln!("rename({:?}, {:?})", old_path, new_path);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            io.ioctx().rename(None, &old_path, &new_path)?;
+
+            Ok(0)
+        }
+        SyscallFunction::SetCurrentDirectory => {
+            let path = arg_user_str(args[0] as usize, args[1] as usize)?;
+
+            debugln!("chdir({:?})", path);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            io.ioctx().set_current_directory(None, &path)?;
+
+            Ok(0)
+        }
+        SyscallFunction::GetCurrentDirectory => {
+            let base = args[0] as usize;
+            let len = args[1] as usize;
+
+            let proc = Process::current();
+            let path = proc.io.lock().ioctx().current_directory_path()?;
+
+            if path.len() > len {
+                return Err(Error::InvalidArgument);
+            }
+            copy_to_user(base, path.as_bytes())?;
+
+            Ok(path.len())
+        }
+        SyscallFunction::CreatePipe => {
+            let fds = args[0] as usize;
+
+            let proc = Process::current();
+            // Make sure the descriptors can be returned before placing them into the table
+            proc.address_space()
+                .validate_user_range(fds, size_of::<[RawFd; 2]>(), true)?;
+
+            let (read, write) = pipe::create()?;
+
+            let mut io = proc.io.lock();
+            let read_fd = io.place_file(read)?;
+            let write_fd = match io.place_file(write) {
+                Ok(fd) => fd,
+                Err(err) => {
+                    io.close_file(read_fd)?;
+                    return Err(err);
+                }
+            };
+            drop(io);
+
+            write_user_value::<[RawFd; 2]>(fds, &[read_fd, write_fd])?;
+
+            Ok(0)
+        }
+        SyscallFunction::Mount => {
+            let options = arg_user_value::<MountOptions>(args[0] as usize)?;
+            let filesystem = arg_user_str(options.filesystem.ptr as usize, options.filesystem.len)?;
+            let target = arg_user_str(options.target.ptr as usize, options.target.len)?;
+
+            debugln!("mount({:?}, {:?})", filesystem, target);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            let node = io.ioctx().find(None, &target, true)?;
+            node.mount(fs::create_filesystem(&filesystem)?)?;
+
+            Ok(0)
+        }
+        SyscallFunction::Unmount => {
+            let target = arg_user_str(args[0] as usize, args[1] as usize)?;
+
+            debugln!("unmount({:?})", target);
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            let node = io.ioctx().find(None, &target, true)?;
+            node.unmount()?;
+
+            Ok(0)
+        }
+        SyscallFunction::DuplicateFd => {
+            let fd = RawFd(args[0] as u32);
+            // Any free descriptor will do if no specific one is requested
+            let target = (args[1] != u64::MAX).then_some(RawFd(args[1] as u32));
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            let new_fd = io.duplicate_file(fd, target)?;
+
+            Ok(new_fd.0 as usize)
+        }
+        SyscallFunction::FileControl => {
+            let fd = RawFd(args[0] as u32);
+            let command =
+                FileControl::try_from(args[1] as u32).map_err(|_| Error::InvalidArgument)?;
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+
+            match command {
+                FileControl::GetFdFlags => Ok(io.file_flags(fd)?.0 as usize),
+                FileControl::SetFdFlags => {
+                    io.set_file_flags(fd, FdFlags(args[2] as u32))?;
+                    Ok(0)
+                }
+                FileControl::GetFileFlags => {
+                    let file = io.file(fd)?;
+                    let file_lock = file.lock();
+                    Ok(file_lock.open_flags().0 as usize)
+                }
+                FileControl::SetFileFlags => {
+                    let flags = OpenFlags(args[2] as u32);
+                    let file = io.file(fd)?;
+                    let mut file_lock = file.lock();
+                    file_lock.set_non_blocking(flags.is_non_blocking());
+                    Ok(0)
+                }
+            }
+        }
+        SyscallFunction::Poll => {
+            let base = args[0] as usize;
+            let count = args[1] as usize;
+            // Waiting without a timeout is requested by passing all ones as the seconds
+            let timeout = if args[2] == u64::MAX {
+                None
+            } else if args[3] >= 1_000_000_000 {
+                return Err(Error::InvalidArgument);
+            } else {
+                Some(Duration::new(args[2], args[3] as u32))
+            };
+
+            let proc = Process::current();
+            // Same as with the descriptor table itself, there's no point in watching more entries
+            if count > proc.io.lock().fd_limit() {
+                return Err(Error::InvalidArgument);
+            }
+            let mut fds = arg_user_vec::<PollFd>(base, count)?;
+
+            let ready = poll(&mut fds, timeout)?;
+            write_user_slice(base, &fds)?;
+
+            Ok(ready)
         }
         SyscallFunction::Close => {
             let fd = RawFd(args[0] as u32);
 
             let proc = Process::current();
             let mut io = proc.io.lock();
-            io.close_file(fd).into_syscall_result() as u64
+            io.close_file(fd)?;
+
+            Ok(0)
+        }
+        SyscallFunction::Spawn => {
+            let options = arg_user_value::<SpawnOptions>(args[0] as usize)?;
+
+            spawn(&options)
+        }
+        SyscallFunction::Fork => fork(frame),
+        SyscallFunction::WaitProcess => {
+            let pid = args[0] as ProcessId;
+            let status = args[1] as usize;
+
+            let proc = Process::current();
+            // Make sure the status can be stored before the child is reaped
+            proc.address_space()
+                .validate_user_range(status, size_of::<i32>(), true)?;
+
+            let code = proc.wait_child(pid)?;
+            write_user_value(status, &code)?;
+
+            Ok(0)
         }
     }
 }
+
+/// Entrypoint for system calls that takes raw argument values along with the exception frame
+/// they were issued from
+pub fn raw_syscall_handler(func: u64, args: &[u64], frame: &ExceptionFrame) -> u64 {
+    let result = match SyscallFunction::try_from(func as usize) {
+        Ok(func) => syscall_handler(func, args, frame),
+        Err(_) => Err(Error::UndefinedSyscall),
+    };
+
+    result.into_syscall_result() as u64
+}
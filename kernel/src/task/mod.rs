@@ -55,6 +55,12 @@ impl ProcessList {
             .iter()
             .find_map(|(i, p)| if *i == id { Some(p) } else { None })
     }
+
+    /// Removes a process from the list, returning it if it was present
+    pub fn remove(&mut self, id: ProcessId) -> Option<Rc<Process>> {
+        let index = self.data.iter().position(|(i, _)| *i == id)?;
+        Some(self.data.remove(index).1)
+    }
 }
 
 /// Global shared process list
@@ -1,15 +1,18 @@
 //! Process data structures
 use core::sync::atomic::{AtomicU32, Ordering};
 
-use alloc::rc::Rc;
+use aarch64_cpu::registers::TTBR0_EL1;
+use abi::error::Error;
+use alloc::{rc::Rc, vec::Vec};
 use atomic_enum::atomic_enum;
+use tock_registers::interfaces::Writeable;
 
 use crate::{
     arch::aarch64::{context::TaskContext, cpu::Cpu},
     mem::table::AddressSpace,
     proc::{
         io::ProcessIo,
-        wait::{Wait, WaitStatus},
+        wait::{self, Wait, WaitStatus},
     },
     sync::{IrqGuard, IrqSafeSpinlock},
     util::OneTimeInit,
@@ -32,10 +35,16 @@ pub enum ProcessState {
 }
 
 struct ProcessInner {
-    pending_wait: Option<&'static Wait>,
     wait_status: WaitStatus,
+    wait_token: u64,
+    exit_status: i32,
+    parent: Option<ProcessId>,
+    children: Vec<ProcessId>,
 }
 
+/// Notified whenever any process terminates
+static PROCESS_EXIT_WAIT: Wait = Wait::new("process_exit");
+
 /// Process data and state structure
 pub struct Process {
     context: TaskContext,
@@ -57,23 +66,37 @@ impl Process {
     ///
     /// Has side-effect of allocating a new PID for itself.
     pub fn new_with_context(space: Option<AddressSpace>, context: TaskContext) -> Rc<Self> {
-        let this = Rc::new(Self {
+        let this = Self::new_unregistered(space, context);
+        this.register();
+        this
+    }
+
+    /// Creates a process from raw architecture-specific [TaskContext] without allocating a PID
+    /// for it. Until [Process::register] is called, the process is not visible to the rest of the
+    /// system and can be dropped if its setup fails.
+    pub fn new_unregistered(space: Option<AddressSpace>, context: TaskContext) -> Rc<Self> {
+        Rc::new(Self {
             context,
             id: OneTimeInit::new(),
             state: AtomicProcessState::new(ProcessState::Suspended),
             cpu_id: AtomicU32::new(0),
             inner: IrqSafeSpinlock::new(ProcessInner {
-                pending_wait: None,
                 wait_status: WaitStatus::Done,
+                wait_token: 0,
+                exit_status: 0,
+                parent: None,
+                children: Vec::new(),
             }),
             space,
             io: IrqSafeSpinlock::new(ProcessIo::new()),
-        });
-
-        let id = unsafe { PROCESSES.lock().push(this.clone()) };
-        this.id.init(id);
+        })
+    }
 
-        this
+    /// Allocates a PID for the process and inserts it into the global process list
+    pub fn register(self: &Rc<Self>) -> ProcessId {
+        let id = unsafe { PROCESSES.lock().push(self.clone()) };
+        self.id.init(id);
+        id
     }
 
     /// Returns a reference to the inner architecture-specific [TaskContext].
@@ -115,6 +138,11 @@ impl Process {
         self.space.as_ref().unwrap()
     }
 
+    /// Returns the address space of the task, if it has one
+    pub fn get_address_space(&self) -> Option<&AddressSpace> {
+        self.space.as_ref()
+    }
+
     /// Selects a suitable CPU queue and submits the process for execution.
     ///
     /// # Panics
@@ -147,54 +175,40 @@ impl Process {
         }
     }
 
-    /// Marks the process as suspended, blocking it from being run until it's resumed.
+    /// Suspends the current process until its pending wait is finished and returns the status
+    /// the wait finished with.
     ///
     /// # Note
     ///
-    /// The process may not halt its execution immediately when this function is called, only when
-    /// this function is called targeting the *current process* running on *local* CPU.
-    ///
-    /// # TODO
-    ///
-    /// The code currently does not allow suspension of active processes on either local or other
-    /// CPUs.
-    pub fn suspend(&self) {
+    /// Only meant to be called by the process itself, on the CPU it's running on.
+    pub fn suspend_wait(&self) -> WaitStatus {
         let _irq = IrqGuard::acquire();
-        let current_state = self.state.swap(ProcessState::Suspended, Ordering::SeqCst);
 
-        match current_state {
-            // NOTE: I'm not sure if the process could've been queued between the store and this
-            //       but most likely not (if I'm not that bad with atomics)
-            // Do nothing, its queue will just drop the process
-            ProcessState::Ready => (),
-            // Do nothing, not in a queue already
-            ProcessState::Suspended => (),
-            ProcessState::Terminated => panic!("Process is terminated"),
-            ProcessState::Running => {
-                let cpu_id = self.cpu_id.load(Ordering::Acquire);
-                let local_cpu_id = Cpu::local_id();
-                let queue = Cpu::local().queue();
-
-                if cpu_id == local_cpu_id {
-                    // Suspending a process running on local CPU
-                    unsafe { queue.yield_cpu() }
-                } else {
-                    todo!();
-                }
+        loop {
+            let inner = self.inner.lock();
+            if !matches!(inner.wait_status, WaitStatus::Pending) {
+                return inner.wait_status;
             }
+            // Suspended under the lock: a wakeup either lands before this and is seen above, or
+            // finds the process suspended and queues it back
+            self.state.store(ProcessState::Suspended, Ordering::SeqCst);
+            drop(inner);
+
+            unsafe { Cpu::local().queue().yield_cpu() }
         }
     }
 
-    /// Sets up a pending wait for the process.
+    /// Sets up a pending wait for the process and returns the token identifying it.
     ///
     /// # Safety
     ///
     /// This function is only meant to be called in no-IRQ context and when caller can guarantee
     /// the task won't get scheduled to a CPU in such state.
-    pub unsafe fn setup_wait(&self, wait: &'static Wait) {
+    pub unsafe fn setup_wait(&self) -> u64 {
         let mut inner = self.inner.lock();
-        inner.pending_wait.replace(wait);
+        inner.wait_token += 1;
         inner.wait_status = WaitStatus::Pending;
+        inner.wait_token
     }
 
     /// Returns current wait status of the task
@@ -202,14 +216,30 @@ impl Process {
         self.inner.lock().wait_status
     }
 
-    /// Updates the wait status for the task.
-    ///
-    /// # Safety
-    ///
-    /// This function is only meant to be called on waiting tasks, otherwise atomicity is not
-    /// guaranteed.
-    pub unsafe fn set_wait_status(&self, status: WaitStatus) {
-        self.inner.lock().wait_status = status;
+    /// Returns the token of the most recent wait set up for the task
+    pub fn wait_token(&self) -> u64 {
+        self.inner.lock().wait_token
+    }
+
+    /// Finishes the wait identified by `token` with given status and resumes the task if it's
+    /// suspended in it. Returns `false` if the task is no longer waiting or a different wait was
+    /// set up since.
+    pub fn complete_wait(self: &Rc<Self>, token: u64, status: WaitStatus) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.wait_token != token || !matches!(inner.wait_status, WaitStatus::Pending) {
+            return false;
+        }
+        inner.wait_status = status;
+
+        // A task which hasn't suspended yet will see the status by itself. A suspended one is
+        // queued back to its own CPU: it may not have switched away yet, but that CPU can only
+        // pick it up after doing so.
+        if self.state() == ProcessState::Suspended {
+            let queue = CpuQueue::for_cpu(self.cpu_id.load(Ordering::Acquire) as usize);
+            self.clone().enqueue_to(queue);
+        }
+
+        true
     }
 
     /// Returns the [Process] currently executing on local CPU, None if idling.
@@ -224,12 +254,100 @@ impl Process {
         Self::get_current().unwrap()
     }
 
+    /// Makes `child` a child of this process, so its exit status can be collected with
+    /// [Process::wait_child]
+    pub fn add_child(&self, child: &Process) {
+        self.inner.lock().children.push(child.id());
+        child.inner.lock().parent = Some(self.id());
+    }
+
+    /// Suspends the current process until its child `pid` terminates, then reaps it and returns
+    /// its exit status
+    pub fn wait_child(&self, pid: ProcessId) -> Result<i32, Error> {
+        if !self.inner.lock().children.contains(&pid) {
+            return Err(Error::DoesNotExist);
+        }
+
+        loop {
+            {
+                // The exit path marks the child terminated under the same lock, so it can't slip
+                // in between the check and the subscription
+                let mut processes = PROCESSES.lock();
+                let Some(child) = processes.get(pid) else {
+                    return Err(Error::DoesNotExist);
+                };
+
+                if child.state() == ProcessState::Terminated {
+                    let status = child.inner.lock().exit_status;
+                    processes.remove(pid);
+                    self.inner.lock().children.retain(|&id| id != pid);
+
+                    return Ok(status);
+                }
+
+                wait::begin_wait();
+                PROCESS_EXIT_WAIT.subscribe();
+            }
+
+            wait::wait_any(None)?;
+        }
+    }
+
     /// Terminate a process
-    pub fn exit(&self, status: usize) {
-        let current_state = self.state.swap(ProcessState::Terminated, Ordering::SeqCst);
+    pub fn exit(self: Rc<Self>, status: i32) {
+        let _irq = IrqGuard::acquire();
+        let current_state = self.state();
 
         debugln!("Process {} exited with code {}", self.id(), status);
 
+        self.io.lock().handle_exit();
+
+        if let Some(space) = &self.space {
+            if current_state == ProcessState::Running {
+                // Still executing in this address space, switch to an empty one before releasing
+                TTBR0_EL1.set(0);
+            }
+
+            unsafe {
+                space.release();
+            }
+        }
+
+        {
+            // The parent checks for termination and orphans its children under the same lock, so
+            // exactly one of the two gets to drop the process from the list
+            let mut processes = PROCESSES.lock();
+
+            let (parent, children) = {
+                let mut inner = self.inner.lock();
+                inner.exit_status = status;
+                (inner.parent, core::mem::take(&mut inner.children))
+            };
+
+            self.state.store(ProcessState::Terminated, Ordering::SeqCst);
+
+            // Nobody is going to wait for the orphans, so the terminated ones can be dropped right
+            // away and the rest will be dropped once they exit
+            for id in children {
+                let Some(child) = processes.get(id) else {
+                    continue;
+                };
+                child.inner.lock().parent = None;
+                if child.state() == ProcessState::Terminated {
+                    processes.remove(id);
+                }
+            }
+
+            if parent.is_none() {
+                processes.remove(self.id());
+            }
+        }
+
+        PROCESS_EXIT_WAIT.wakeup_all();
+
+        // The scheduler keeps the process around until it's switched away from its stack
+        drop(self);
+
         match current_state {
             ProcessState::Suspended => (),
             ProcessState::Ready => todo!(),
@@ -36,6 +36,10 @@ pub struct CpuQueueInner {
 
     /// CPU time usage statistics
     pub stats: CpuQueueStats,
+
+    // Process which exited while running on this CPU. Its stack has to stay around until the CPU
+    // switches away from it, so it is only dropped during the next switch.
+    exited: Option<Rc<Process>>,
 }
 
 /// Per-CPU queue
@@ -100,6 +104,7 @@ impl CpuQueue {
                     current: None,
                     queue: VecDeque::new(),
                     stats: CpuQueueStats::default(),
+                    exited: None,
                 })
             },
             idle,
@@ -123,7 +128,10 @@ impl CpuQueue {
             proc.set_running(Cpu::local_id());
 
             drop(inner);
-            proc.context().enter();
+
+            let context = proc.context() as *const TaskContext;
+            drop(proc);
+            (*context).enter();
         } else {
             drop(inner);
 
@@ -144,13 +152,20 @@ impl CpuQueue {
         let delta = t - inner.stats.measure_time;
         inner.stats.measure_time = t;
 
+        let exited = inner.exited.take();
         let current = inner.current.clone();
 
         if let Some(current) = current.as_ref() {
-            if current.state() == ProcessState::Running {
-                current.set_state(ProcessState::Ready);
+            match current.state() {
+                ProcessState::Running => {
+                    current.set_state(ProcessState::Ready);
+                    inner.queue.push_back(current.clone());
+                }
+                ProcessState::Terminated => inner.exited = Some(current.clone()),
+                // Suspended tasks are queued back by whoever resumes them. A task which is
+                // already Ready was resumed before it could switch away and is queued already.
+                ProcessState::Suspended | ProcessState::Ready => (),
             }
-            inner.queue.push_back(current.clone());
 
             inner.stats.cpu_time += delta;
         } else {
@@ -161,22 +176,29 @@ impl CpuQueue {
 
         inner.current = next.clone();
 
-        // Can drop the lock, we hold current and next Rc's
         drop(inner);
 
-        let (from, _from_rc) = if let Some(current) = current.as_ref() {
-            (current.context(), Rc::strong_count(current))
+        // This CPU has already switched away from the stack of the previously exited process
+        drop(exited);
+
+        let from = if let Some(current) = current.as_ref() {
+            current.context() as *const TaskContext
         } else {
-            (&self.idle, 0)
+            &self.idle as *const TaskContext
         };
 
-        let (to, _to_rc) = if let Some(next) = next.as_ref() {
+        let to = if let Some(next) = next.as_ref() {
             next.set_running(Cpu::local_id());
-            (next.context(), Rc::strong_count(next))
+            next.context() as *const TaskContext
         } else {
-            (&self.idle, 0)
+            &self.idle as *const TaskContext
         };
 
+        // The processes are still referenced from the process list, the queue or the exited slot.
+        // Nothing is left on the stack, as the switch doesn't return to an exited process.
+        drop(current);
+        drop(next);
+
         // if let Some(from) = current.as_ref() {
         //     log_print_raw!(crate::debug::LogLevel::Info, "{}", from.id());
         // } else {
@@ -193,7 +215,7 @@ impl CpuQueue {
 
         // log_print_raw!(crate::debug::LogLevel::Info, "\n");
 
-        to.switch(from)
+        (*to).switch(&*from)
     }
 
     /// Pushes the process to the back of the execution queue.
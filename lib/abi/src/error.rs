@@ -1,15 +1,34 @@
+use core::fmt;
+
 use crate::io::RawFd;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum Error {
-    OutOfMemory = 1,
-    InvalidMemoryOperation,
-    AlreadyExists,
-    TimedOut,
-    InvalidArgument,
-    DoesNotExist,
-    IsADirectory,
-    InvalidFile,
+primitive_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Error: u32 {
+        OutOfMemory = 1,
+        InvalidMemoryOperation = 2,
+        AlreadyExists = 3,
+        TimedOut = 4,
+        InvalidArgument = 5,
+        DoesNotExist = 6,
+        IsADirectory = 7,
+        InvalidFile = 8,
+        UndefinedSyscall = 9,
+        NotImplemented = 10,
+        NotADirectory = 11,
+        PermissionDenied = 12,
+        WouldBlock = 13,
+        Interrupted = 14,
+        BrokenPipe = 15,
+        NotEmpty = 16,
+        ReadOnlyFs = 17,
+        BadDescriptor = 18,
+        NoSpace = 19,
+        NotSupported = 20,
+        SymlinkLoop = 21,
+        CrossDevice = 22,
+        TooManyFiles = 23,
+    }
 }
 
 pub trait FromSyscallResult: Sized {
@@ -25,37 +44,35 @@ pub trait SyscallError {
     fn into_syscall_error(self) -> usize;
 }
 
-impl TryFrom<u32> for Error {
-    type Error = ();
-
-    fn try_from(value: u32) -> Result<Self, ()> {
-        match value {
-            1 => Ok(Self::OutOfMemory),
-            2 => Ok(Self::InvalidMemoryOperation),
-            3 => Ok(Self::AlreadyExists),
-            4 => Ok(Self::TimedOut),
-            5 => Ok(Self::InvalidArgument),
-            6 => Ok(Self::DoesNotExist),
-            7 => Ok(Self::IsADirectory),
-            8 => Ok(Self::InvalidFile),
-
-            _ => Err(()),
-        }
-    }
-}
-
-impl From<Error> for u32 {
-    fn from(value: Error) -> Self {
-        match value {
-            Error::OutOfMemory => 1,
-            Error::InvalidMemoryOperation => 2,
-            Error::AlreadyExists => 3,
-            Error::TimedOut => 4,
-            Error::InvalidArgument => 5,
-            Error::DoesNotExist => 6,
-            Error::IsADirectory => 7,
-            Error::InvalidFile => 8,
-        }
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::OutOfMemory => "Out of memory",
+            Self::InvalidMemoryOperation => "Invalid memory operation",
+            Self::AlreadyExists => "Already exists",
+            Self::TimedOut => "Operation timed out",
+            Self::InvalidArgument => "Invalid argument",
+            Self::DoesNotExist => "No such file or directory",
+            Self::IsADirectory => "Is a directory",
+            Self::InvalidFile => "Invalid file",
+            Self::UndefinedSyscall => "Undefined system call",
+            Self::NotImplemented => "Not implemented",
+            Self::NotADirectory => "Not a directory",
+            Self::PermissionDenied => "Permission denied",
+            Self::WouldBlock => "Operation would block",
+            Self::Interrupted => "Interrupted",
+            Self::BrokenPipe => "Broken pipe",
+            Self::NotEmpty => "Directory not empty",
+            Self::ReadOnlyFs => "Read-only filesystem",
+            Self::BadDescriptor => "Bad file descriptor",
+            Self::NoSpace => "No space left",
+            Self::NotSupported => "Operation not supported",
+            Self::SymlinkLoop => "Too many levels of symbolic links",
+            Self::CrossDevice => "Cross-device link",
+            Self::TooManyFiles => "Too many open files",
+        };
+
+        f.write_str(msg)
     }
 }
 
@@ -107,7 +124,7 @@ impl FromSyscallResult for usize {
 
 impl IntoSyscallResult for usize {
     fn into_syscall_result(self) -> usize {
-        assert!((self as isize) > 0);
+        assert!((self as isize) >= 0);
         self
     }
 }
@@ -1,13 +1,167 @@
-use core::fmt;
+use core::{fmt, time::Duration};
 
 #[derive(Clone, Copy, PartialEq, Debug, PartialOrd, Ord, Eq)]
 pub struct RawFd(pub u32);
 
+/// Pointer and length of a UTF-8 string in the caller's memory, as passed to the kernel
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct RawStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct OpenFlags(pub u32);
 
+/// Flags of a single file descriptor, not shared with its duplicates
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FdFlags(pub u32);
+
+/// Readiness events a descriptor can be polled for
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct PollEvents(pub u32);
+
 const O_READ: u32 = 1 << 0;
 const O_WRITE: u32 = 1 << 1;
+const O_CREATE: u32 = 1 << 2;
+const O_TRUNCATE: u32 = 1 << 3;
+const O_APPEND: u32 = 1 << 4;
+const O_EXCL: u32 = 1 << 5;
+const O_CLOEXEC: u32 = 1 << 6;
+const O_NONBLOCK: u32 = 1 << 7;
+
+const FD_CLOEXEC: u32 = 1 << 0;
+
+primitive_enum! {
+    /// Operations of the file descriptor control call
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum FileControl: u32 {
+        /// Returns the [FdFlags] of the descriptor
+        GetFdFlags = 1,
+        /// Replaces the [FdFlags] of the descriptor
+        SetFdFlags = 2,
+        /// Returns the [OpenFlags] of the file, which are shared by all its descriptors
+        GetFileFlags = 3,
+        /// Changes the non-blocking mode of the file, other [OpenFlags] are ignored
+        SetFileFlags = 4,
+    }
+}
+
+/// Describes the position a seek operation is relative to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Offset from the start of the file
+    Start(u64),
+    /// Offset from the end of the file
+    End(i64),
+    /// Offset from the current position
+    Current(i64),
+}
+
+/// Descriptor watched by the poll call
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct PollFd {
+    /// Descriptor to watch
+    pub fd: RawFd,
+    /// Events the caller is interested in
+    pub events: PollEvents,
+    /// Events which are ready, filled in by the kernel
+    pub revents: PollEvents,
+}
+
+/// Describes a filesystem to be attached to the directory tree
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct MountOptions {
+    /// Name of the filesystem kind to create
+    pub filesystem: RawStr,
+    /// Path to the directory the filesystem is mounted over
+    pub target: RawStr,
+}
+
+primitive_enum! {
+    /// Describes the kind of a filesystem node
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum FileType: u32 {
+        File = 1,
+        Directory = 2,
+        Symlink = 3,
+        Char = 4,
+        Block = 5,
+        Pipe = 6,
+    }
+}
+
+/// Directory entry, as returned by the directory reading call
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct DirectoryEntry {
+    /// Identifier of the node the entry refers to
+    pub id: u64,
+    /// Kind of the node
+    pub ty: FileType,
+    /// NUL-padded entry name
+    pub name: [u8; DirectoryEntry::NAME_MAX + 1],
+}
+
+impl DirectoryEntry {
+    /// Maximum length of an entry name
+    pub const NAME_MAX: usize = 255;
+
+    /// Returns the name of the entry
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(Self::NAME_MAX);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+
+impl fmt::Debug for DirectoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirectoryEntry")
+            .field("id", &self.id)
+            .field("ty", &self.ty)
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// Access permission bits of a filesystem node
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct FileMode(pub u32);
+
+/// Information about a filesystem node
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct FileMetadata {
+    /// Identifier of the node
+    pub id: u64,
+    /// Size of the node's contents in bytes
+    pub size: u64,
+    /// Kind of the node
+    pub ty: FileType,
+    /// Access permissions
+    pub mode: FileMode,
+    /// Owner user ID
+    pub uid: u32,
+    /// Owner group ID
+    pub gid: u32,
+    /// Device number, for device nodes
+    pub device: u64,
+    /// Time of the last metadata change
+    pub ctime: Duration,
+    /// Time of the last contents modification
+    pub mtime: Duration,
+    /// Time of the last access
+    pub atime: Duration,
+}
 
 impl RawFd {
     pub const STDOUT: Self = Self(1);
@@ -36,6 +190,175 @@ impl OpenFlags {
     pub const fn is_write(self) -> bool {
         self.0 & O_WRITE != 0
     }
+
+    pub const fn create(mut self) -> Self {
+        self.0 |= O_CREATE;
+        self
+    }
+
+    pub const fn truncate(mut self) -> Self {
+        self.0 |= O_TRUNCATE;
+        self
+    }
+
+    pub const fn append(mut self) -> Self {
+        self.0 |= O_APPEND;
+        self
+    }
+
+    pub const fn exclusive(mut self) -> Self {
+        self.0 |= O_EXCL;
+        self
+    }
+
+    pub const fn is_create(self) -> bool {
+        self.0 & O_CREATE != 0
+    }
+
+    pub const fn is_truncate(self) -> bool {
+        self.0 & O_TRUNCATE != 0
+    }
+
+    pub const fn is_append(self) -> bool {
+        self.0 & O_APPEND != 0
+    }
+
+    pub const fn is_exclusive(self) -> bool {
+        self.0 & O_EXCL != 0
+    }
+
+    pub const fn close_on_exec(mut self) -> Self {
+        self.0 |= O_CLOEXEC;
+        self
+    }
+
+    pub const fn is_close_on_exec(self) -> bool {
+        self.0 & O_CLOEXEC != 0
+    }
+
+    pub const fn non_blocking(mut self) -> Self {
+        self.0 |= O_NONBLOCK;
+        self
+    }
+
+    pub const fn is_non_blocking(self) -> bool {
+        self.0 & O_NONBLOCK != 0
+    }
+}
+
+impl FdFlags {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub const fn close_on_exec(mut self) -> Self {
+        self.0 |= FD_CLOEXEC;
+        self
+    }
+
+    pub const fn is_close_on_exec(self) -> bool {
+        self.0 & FD_CLOEXEC != 0
+    }
+}
+
+impl PollEvents {
+    /// Descriptor can be read from without blocking
+    pub const READ: Self = Self(1 << 0);
+    /// Descriptor can be written to without blocking
+    pub const WRITE: Self = Self(1 << 1);
+    /// Descriptor can't be polled for the requested events, only reported by the kernel
+    pub const ERROR: Self = Self(1 << 2);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl PollFd {
+    pub const fn new(fd: RawFd, events: PollEvents) -> Self {
+        Self {
+            fd,
+            events,
+            revents: PollEvents::empty(),
+        }
+    }
+}
+
+impl RawStr {
+    /// Describes the memory of `value`, which must stay alive for as long as the result is used
+    pub fn new(value: &str) -> Self {
+        Self {
+            ptr: value.as_ptr(),
+            len: value.len(),
+        }
+    }
+}
+
+impl SeekFrom {
+    /// Splits the value into its kind and offset for passing through a system call
+    pub fn into_raw(self) -> (usize, usize) {
+        match self {
+            Self::Start(offset) => (0, offset as usize),
+            Self::Current(offset) => (1, offset as usize),
+            Self::End(offset) => (2, offset as usize),
+        }
+    }
+
+    /// Reconstructs the value from its system call representation
+    pub fn from_raw(kind: usize, offset: usize) -> Option<Self> {
+        match kind {
+            0 => Some(Self::Start(offset as u64)),
+            1 => Some(Self::Current(offset as i64)),
+            2 => Some(Self::End(offset as i64)),
+            _ => None,
+        }
+    }
+}
+
+impl FileMode {
+    pub const USER_READ: Self = Self(0o400);
+    pub const USER_WRITE: Self = Self(0o200);
+    pub const USER_EXEC: Self = Self(0o100);
+    pub const GROUP_READ: Self = Self(0o040);
+    pub const GROUP_WRITE: Self = Self(0o020);
+    pub const GROUP_EXEC: Self = Self(0o010);
+    pub const OTHER_READ: Self = Self(0o004);
+    pub const OTHER_WRITE: Self = Self(0o002);
+    pub const OTHER_EXEC: Self = Self(0o001);
+
+    pub const fn new(bits: u32) -> Self {
+        Self(bits & 0o777)
+    }
+
+    pub const fn default_file() -> Self {
+        Self(0o644)
+    }
+
+    pub const fn default_dir() -> Self {
+        Self(0o755)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Debug for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileMode({:#o})", self.0)
+    }
 }
 
 impl fmt::Debug for OpenFlags {
@@ -43,6 +366,12 @@ impl fmt::Debug for OpenFlags {
         f.debug_struct("OpenFlags")
             .field("read", &(self.is_read()))
             .field("write", &(self.is_write()))
+            .field("create", &(self.is_create()))
+            .field("truncate", &(self.is_truncate()))
+            .field("append", &(self.is_append()))
+            .field("exclusive", &(self.is_exclusive()))
+            .field("close_on_exec", &(self.is_close_on_exec()))
+            .field("non_blocking", &(self.is_non_blocking()))
             .finish()
     }
 }
@@ -1,57 +1,44 @@
 #![no_std]
 
+#[macro_use]
+mod macros;
+
 pub mod error;
 pub mod io;
 pub mod path;
-
-#[derive(Clone, Copy, Debug)]
-pub enum SyscallFunction {
-    Exit = 1,
-    Nanosleep = 2,
-    MapMemory = 3,
-    UnmapMemory = 4,
-    Write = 5,
-    Read = 6,
-    Open = 7,
-    Close = 8,
-
-    DebugTrace = 128,
-}
-
-impl TryFrom<usize> for SyscallFunction {
-    type Error = ();
-
-    fn try_from(value: usize) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(Self::Exit),
-            2 => Ok(Self::Nanosleep),
-            3 => Ok(Self::MapMemory),
-            4 => Ok(Self::UnmapMemory),
-            5 => Ok(Self::Write),
-            6 => Ok(Self::Read),
-            7 => Ok(Self::Open),
-            8 => Ok(Self::Close),
-
-            128 => Ok(Self::DebugTrace),
-
-            _ => Err(()),
-        }
-    }
-}
-
-impl From<SyscallFunction> for usize {
-    fn from(value: SyscallFunction) -> Self {
-        match value {
-            SyscallFunction::Exit => 1,
-            SyscallFunction::Nanosleep => 2,
-            SyscallFunction::MapMemory => 3,
-            SyscallFunction::UnmapMemory => 4,
-            SyscallFunction::Write => 5,
-            SyscallFunction::Read => 6,
-            SyscallFunction::Open => 7,
-            SyscallFunction::Close => 8,
-
-            SyscallFunction::DebugTrace => 128,
-        }
+pub mod process;
+
+primitive_enum! {
+    #[derive(Clone, Copy, Debug)]
+    pub enum SyscallFunction: usize {
+        Exit = 1,
+        Nanosleep = 2,
+        MapMemory = 3,
+        UnmapMemory = 4,
+        Write = 5,
+        Read = 6,
+        Open = 7,
+        Close = 8,
+        Spawn = 9,
+        WaitProcess = 10,
+        Fork = 11,
+        Mount = 12,
+        Unmount = 13,
+        OpenDirectory = 14,
+        ReadDirectory = 15,
+        GetMetadata = 16,
+        FileMetadata = 17,
+        Seek = 18,
+        CreateDirectory = 19,
+        Remove = 20,
+        Rename = 21,
+        SetCurrentDirectory = 22,
+        GetCurrentDirectory = 23,
+        CreatePipe = 24,
+        DuplicateFd = 25,
+        FileControl = 26,
+        Poll = 27,
+
+        DebugTrace = 128,
     }
 }
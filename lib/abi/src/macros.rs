@@ -0,0 +1,39 @@
+/// Defines an enum with explicit discriminants along with conversions to and from its primitive
+/// representation, so the mapping only has to be written once
+macro_rules! primitive_enum {
+    (
+        $(#[$enum_meta:meta])*
+        $vis:vis enum $name:ident: $repr:ty {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:literal
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$enum_meta])*
+        #[repr($repr)]
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant = $value
+            ),+
+        }
+
+        impl TryFrom<$repr> for $name {
+            type Error = ();
+
+            fn try_from(value: $repr) -> Result<Self, ()> {
+                match value {
+                    $($value => Ok(Self::$variant),)+
+                    _ => Err(()),
+                }
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> $repr {
+                value as $repr
+            }
+        }
+    };
+}
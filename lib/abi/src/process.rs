@@ -0,0 +1,101 @@
+use crate::io::{RawFd, RawStr};
+
+/// Optional arguments controlling how a new process is set up
+#[derive(Clone, Copy, Debug)]
+pub enum SpawnOption {
+    /// Makes the file referred to by `source` descriptor of the caller available to the child
+    /// process as `child` descriptor
+    InheritFile { source: RawFd, child: RawFd },
+    /// Sets the maximum number of descriptors the child process can have, instead of inheriting
+    /// the limit of the caller. The limit can't exceed the caller's one and has to cover all the
+    /// descriptors the child inherits.
+    FdLimit(u32),
+}
+
+/// [SpawnOption] in the form it is passed to the kernel: an explicit tag followed by the payload
+/// of the option
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct RawSpawnOption {
+    pub tag: u32,
+    pub payload: [u32; 2],
+}
+
+/// Describes a program to be loaded into a new process
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct SpawnOptions {
+    /// Path to the program image, resolved relative to the caller's I/O context
+    pub program: RawStr,
+    /// Arguments passed to the program, including the program name itself
+    pub arguments: *const RawStr,
+    /// Number of entries in `arguments`
+    pub argument_count: usize,
+    /// Additional options for process creation
+    pub optional: *const RawSpawnOption,
+    /// Number of entries in `optional`
+    pub optional_count: usize,
+}
+
+impl SpawnOption {
+    /// Converts the option into its system call representation
+    pub fn into_raw(self) -> RawSpawnOption {
+        match self {
+            Self::InheritFile { source, child } => RawSpawnOption {
+                tag: 1,
+                payload: [source.0, child.0],
+            },
+            Self::FdLimit(limit) => RawSpawnOption {
+                tag: 2,
+                payload: [limit, 0],
+            },
+        }
+    }
+
+    /// Reconstructs the option from its system call representation
+    pub fn from_raw(raw: RawSpawnOption) -> Option<Self> {
+        match raw.tag {
+            1 => Some(Self::InheritFile {
+                source: RawFd(raw.payload[0]),
+                child: RawFd(raw.payload[1]),
+            }),
+            2 => Some(Self::FdLimit(raw.payload[0])),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::RawFd;
+
+    use super::{RawSpawnOption, SpawnOption};
+
+    #[test]
+    fn test_spawn_option_raw() {
+        let raw = SpawnOption::FdLimit(16).into_raw();
+        assert!(matches!(
+            SpawnOption::from_raw(raw),
+            Some(SpawnOption::FdLimit(16))
+        ));
+
+        let raw = SpawnOption::InheritFile {
+            source: RawFd(3),
+            child: RawFd(0),
+        }
+        .into_raw();
+        assert!(matches!(
+            SpawnOption::from_raw(raw),
+            Some(SpawnOption::InheritFile {
+                source: RawFd(3),
+                child: RawFd(0)
+            })
+        ));
+
+        let raw = RawSpawnOption {
+            tag: 0,
+            payload: [0; 2],
+        };
+        assert!(SpawnOption::from_raw(raw).is_none());
+    }
+}
@@ -1,19 +1,52 @@
-use abi::error::Error;
+use abi::{
+    error::Error,
+    io::{FileMetadata, FileMode},
+};
 
-use crate::node::{VnodeImpl, VnodeRef};
+use alloc::sync::Arc;
 
-pub trait CharDevice {
+use crate::{
+    node::{VnodeImpl, VnodeRef},
+    stream::{Stream, StreamRef},
+};
+
+pub trait CharDevice: Sync {
     fn read(&'static self, blocking: bool, data: &mut [u8]) -> Result<usize, Error>;
     fn write(&self, blocking: bool, data: &[u8]) -> Result<usize, Error>;
+
+    fn is_ready(&self, _write: bool) -> Result<bool, Error> {
+        Ok(true)
+    }
 }
 
 pub struct CharDeviceWrapper {
-    device: &'static dyn CharDevice,
+    stream: StreamRef,
+    device_id: u64,
 }
 
+// Lets the device be used without holding the lock of its node
+struct DeviceStream(&'static dyn CharDevice);
+
 impl CharDeviceWrapper {
-    pub const fn new(device: &'static dyn CharDevice) -> Self {
-        Self { device }
+    pub fn new(device: &'static dyn CharDevice, device_id: u64) -> Self {
+        Self {
+            stream: Arc::new(DeviceStream(device)),
+            device_id,
+        }
+    }
+}
+
+impl Stream for DeviceStream {
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Error> {
+        self.0.read(blocking, data)
+    }
+
+    fn write(&self, blocking: bool, data: &[u8]) -> Result<usize, Error> {
+        self.0.write(blocking, data)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Error> {
+        self.0.is_ready(write)
     }
 }
 
@@ -27,11 +60,21 @@ impl VnodeImpl for CharDeviceWrapper {
     }
 
     fn read(&mut self, _node: &VnodeRef, _pos: usize, data: &mut [u8]) -> Result<usize, Error> {
-        self.device.read(true, data)
+        self.stream.read(true, data)
     }
 
     fn write(&mut self, _node: &VnodeRef, _pos: usize, data: &[u8]) -> Result<usize, Error> {
-        self.device.write(true, data)
+        self.stream.write(true, data)
+    }
+
+    fn stream(&mut self) -> Option<StreamRef> {
+        Some(self.stream.clone())
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, metadata: &mut FileMetadata) -> Result<(), Error> {
+        metadata.mode = FileMode::new(0o660);
+        metadata.device = self.device_id;
+        Ok(())
     }
 
     fn create(
@@ -40,6 +83,6 @@ impl VnodeImpl for CharDeviceWrapper {
         _name: &str,
         _kind: crate::node::VnodeKind,
     ) -> Result<VnodeRef, Error> {
-        todo!()
+        Err(Error::NotImplemented)
     }
 }
@@ -1,30 +1,36 @@
-use core::cell::RefCell;
-
-use abi::error::Error;
-use alloc::rc::Rc;
+use abi::{
+    error::Error,
+    io::{DirectoryEntry, FileMetadata, OpenFlags, SeekFrom},
+};
+use alloc::sync::Arc;
 use bitflags::bitflags;
 
-use crate::{
-    node::{VnodeKind, VnodeRef},
-    Read, Write,
-};
+use crate::{node::VnodeRef, sync::Lock, Read, Write};
 
 bitflags! {
     pub struct FileFlags: u32 {
         const READ = 1 << 0;
         const WRITE = 1 << 1;
+        const APPEND = 1 << 2;
+        const NONBLOCK = 1 << 3;
     }
 }
 
-pub type FileRef = Rc<RefCell<File>>;
+pub type FileRef = Arc<Lock<File>>;
 
 pub struct NormalFile {
     vnode: VnodeRef,
     pos: usize,
 }
 
+pub struct DirectoryFile {
+    vnode: VnodeRef,
+    pos: usize,
+}
+
 pub enum FileInner {
     Normal(NormalFile),
+    Directory(DirectoryFile),
 }
 
 pub struct File {
@@ -34,27 +40,192 @@ pub struct File {
 
 impl File {
     pub fn normal(vnode: VnodeRef, pos: usize, flags: FileFlags) -> FileRef {
-        Rc::new(RefCell::new(Self {
+        Arc::new(Lock::new(Self {
             inner: FileInner::Normal(NormalFile { vnode, pos }),
             flags,
         }))
     }
+
+    pub fn directory(vnode: VnodeRef) -> FileRef {
+        Arc::new(Lock::new(Self {
+            inner: FileInner::Directory(DirectoryFile { vnode, pos: 0 }),
+            flags: FileFlags::READ,
+        }))
+    }
+
+    pub fn seek(&mut self, from: SeekFrom) -> Result<usize, Error> {
+        match &mut self.inner {
+            FileInner::Normal(inner) => {
+                if inner.vnode.kind().is_stream() {
+                    return Err(Error::NotSupported);
+                }
+
+                let pos = match from {
+                    SeekFrom::Start(offset) => Some(offset as usize),
+                    SeekFrom::Current(offset) => inner.pos.checked_add_signed(offset as isize),
+                    SeekFrom::End(offset) => {
+                        inner.vnode.size()?.checked_add_signed(offset as isize)
+                    }
+                };
+
+                inner.pos = pos.ok_or(Error::InvalidArgument)?;
+                Ok(inner.pos)
+            }
+            // Directories can only be rewound to a previously reached position
+            FileInner::Directory(inner) => match from {
+                SeekFrom::Start(offset) => {
+                    inner.pos = offset as usize;
+                    Ok(inner.pos)
+                }
+                _ => Err(Error::InvalidArgument),
+            },
+        }
+    }
+
+    pub fn metadata(&self) -> Result<FileMetadata, Error> {
+        match &self.inner {
+            FileInner::Normal(inner) => inner.vnode.metadata(),
+            FileInner::Directory(inner) => inner.vnode.metadata(),
+        }
+    }
+
+    pub fn open_flags(&self) -> OpenFlags {
+        let mut flags = OpenFlags::new();
+
+        if self.flags.contains(FileFlags::READ) {
+            flags = flags.read();
+        }
+        if self.flags.contains(FileFlags::WRITE) {
+            flags = flags.write();
+        }
+        if self.flags.contains(FileFlags::APPEND) {
+            flags = flags.append();
+        }
+        if self.flags.contains(FileFlags::NONBLOCK) {
+            flags = flags.non_blocking();
+        }
+
+        flags
+    }
+
+    pub fn set_non_blocking(&mut self, non_blocking: bool) {
+        self.flags.set(FileFlags::NONBLOCK, non_blocking);
+    }
+
+    // Returns whether the file can be read from or written to without blocking
+    pub fn is_ready(&self, write: bool) -> Result<bool, Error> {
+        let required = if write {
+            FileFlags::WRITE
+        } else {
+            FileFlags::READ
+        };
+        if !self.flags.contains(required) {
+            return Err(Error::BadDescriptor);
+        }
+
+        match &self.inner {
+            FileInner::Normal(inner) => inner.vnode.is_ready(write),
+            FileInner::Directory(_) => Ok(true),
+        }
+    }
+
+    // I/O on streams can block, so it's done without keeping the file locked. Streams have no
+    // position, so there's nothing in the file for it to update.
+    pub fn read_shared(file: &FileRef, data: &mut [u8]) -> Result<usize, Error> {
+        let stream = file.lock().stream_node(false)?;
+
+        match stream {
+            Some((node, true)) => node.read_nonblocking(0, data),
+            Some((node, false)) => node.read(0, data),
+            None => file.lock().read(data),
+        }
+    }
+
+    pub fn write_shared(file: &FileRef, data: &[u8]) -> Result<usize, Error> {
+        let stream = file.lock().stream_node(true)?;
+
+        match stream {
+            Some((node, true)) => node.write_nonblocking(0, data),
+            Some((node, false)) => node.write(0, data),
+            None => file.lock().write(data),
+        }
+    }
+
+    // Returns the node of a stream file along with whether it's in non-blocking mode
+    fn stream_node(&self, write: bool) -> Result<Option<(VnodeRef, bool)>, Error> {
+        let required = if write {
+            FileFlags::WRITE
+        } else {
+            FileFlags::READ
+        };
+        if !self.flags.contains(required) {
+            return Err(Error::BadDescriptor);
+        }
+
+        match &self.inner {
+            FileInner::Normal(inner) if inner.vnode.kind().is_stream() => Ok(Some((
+                inner.vnode.clone(),
+                self.flags.contains(FileFlags::NONBLOCK),
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn read_dir(&mut self, entries: &mut [DirectoryEntry]) -> Result<usize, Error> {
+        let FileInner::Directory(inner) = &mut self.inner else {
+            return Err(Error::NotADirectory);
+        };
+
+        let mut count = 0;
+
+        while count < entries.len() {
+            let Some(node) = inner.vnode.read_dir(inner.pos)? else {
+                break;
+            };
+            inner.pos += 1;
+
+            let name = node.name();
+            let name = name.as_bytes();
+            let name = &name[..name.len().min(DirectoryEntry::NAME_MAX)];
+            let entry = &mut entries[count];
+
+            entry.id = node.id();
+            entry.ty = node.kind().into();
+            entry.name.fill(0);
+            entry.name[..name.len()].copy_from_slice(name);
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
 }
 
 impl Write for File {
     fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
         if !self.flags.contains(FileFlags::WRITE) {
-            panic!();
+            return Err(Error::BadDescriptor);
         }
 
         match &mut self.inner {
             FileInner::Normal(inner) => {
-                let count = inner.vnode.write(inner.pos, data)?;
-                if inner.vnode.kind() != VnodeKind::Char {
+                if self.flags.contains(FileFlags::APPEND) && !inner.vnode.kind().is_stream() {
+                    let (pos, count) = inner.vnode.append(data)?;
+                    inner.pos = pos + count;
+                    return Ok(count);
+                }
+
+                let count = if self.flags.contains(FileFlags::NONBLOCK) {
+                    inner.vnode.write_nonblocking(inner.pos, data)?
+                } else {
+                    inner.vnode.write(inner.pos, data)?
+                };
+                if !inner.vnode.kind().is_stream() {
                     inner.pos += count;
                 }
                 Ok(count)
             }
+            FileInner::Directory(_) => Err(Error::IsADirectory),
         }
     }
 }
@@ -62,17 +233,22 @@ impl Write for File {
 impl Read for File {
     fn read(&mut self, data: &mut [u8]) -> Result<usize, Error> {
         if !self.flags.contains(FileFlags::READ) {
-            panic!();
+            return Err(Error::BadDescriptor);
         }
 
         match &mut self.inner {
             FileInner::Normal(inner) => {
-                let count = inner.vnode.read(inner.pos, data)?;
-                if inner.vnode.kind() != VnodeKind::Char {
+                let count = if self.flags.contains(FileFlags::NONBLOCK) {
+                    inner.vnode.read_nonblocking(inner.pos, data)?
+                } else {
+                    inner.vnode.read(inner.pos, data)?
+                };
+                if !inner.vnode.kind().is_stream() {
                     inner.pos += count;
                 }
                 Ok(count)
             }
+            FileInner::Directory(_) => Err(Error::IsADirectory),
         }
     }
 }
@@ -83,6 +259,7 @@ impl Drop for File {
             FileInner::Normal(inner) => {
                 inner.vnode.close().ok();
             }
+            FileInner::Directory(_) => (),
         }
     }
 }
@@ -1,15 +1,15 @@
 use core::{any::Any, cell::Ref};
 
-use alloc::rc::Rc;
+use alloc::sync::Arc;
 
 use abi::error::Error;
 
 use crate::{block::BlockDevice, node::VnodeRef};
 
-pub trait Filesystem {
-    fn root(self: Rc<Self>) -> Result<VnodeRef, Error>;
+pub trait Filesystem: Send + Sync {
+    fn root(self: Arc<Self>) -> Result<VnodeRef, Error>;
 
-    fn dev(self: Rc<Self>) -> Option<&'static dyn BlockDevice>;
+    fn dev(self: Arc<Self>) -> Option<&'static dyn BlockDevice>;
 
     fn data(&self) -> Option<Ref<dyn Any>>;
 }
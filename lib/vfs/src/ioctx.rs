@@ -1,7 +1,15 @@
 use abi::{error::Error, io::OpenFlags, path};
+use alloc::string::String;
 
-use crate::{file::FileRef, node::VnodeRef};
+use crate::{
+    file::FileRef,
+    node::{VnodeKind, VnodeRef},
+};
 
+// Limit on the number of nested symlinks followed while resolving a single path
+const MAX_SYMLINK_DEPTH: usize = 16;
+
+#[derive(Clone)]
 pub struct IoContext {
     root: VnodeRef,
     cwd: VnodeRef,
@@ -15,7 +23,54 @@ impl IoContext {
         }
     }
 
-    fn _find(&self, mut at: VnodeRef, path: &str, follow: bool) -> Result<VnodeRef, Error> {
+    #[inline]
+    pub fn current_directory(&self) -> &VnodeRef {
+        &self.cwd
+    }
+
+    pub fn set_current_directory(&mut self, at: Option<VnodeRef>, path: &str) -> Result<(), Error> {
+        let node = self.find(at, path, true)?;
+
+        if !node.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        self.cwd = node;
+        Ok(())
+    }
+
+    pub fn current_directory_path(&self) -> Result<String, Error> {
+        self.cwd.path_from(&self.root)
+    }
+
+    fn resolve_link(&self, link: VnodeRef, depth: usize) -> Result<VnodeRef, Error> {
+        if depth >= MAX_SYMLINK_DEPTH {
+            return Err(Error::SymlinkLoop);
+        }
+
+        let target = link.read_link()?;
+        if target.is_empty() {
+            return Err(Error::DoesNotExist);
+        }
+
+        // Relative targets are resolved from the directory containing the link
+        let (at, path) = if let Some(path) = target.strip_prefix('/') {
+            (self.root.clone(), path.trim_start_matches('/'))
+        } else {
+            (link.parent(), target.as_str())
+        };
+
+        self._find(at, path, true, depth + 1)
+    }
+
+    fn _find(
+        &self,
+        at: VnodeRef,
+        path: &str,
+        follow: bool,
+        depth: usize,
+    ) -> Result<VnodeRef, Error> {
+        let mut at = at.resolve_mount();
         let mut element;
         let mut rest = path;
 
@@ -23,30 +78,33 @@ impl IoContext {
             (element, rest) = path::split_left(rest);
 
             if !at.is_directory() {
-                todo!();
+                return Err(Error::NotADirectory);
             }
 
             match element {
                 path::PARENT_NAME => {
-                    at = at.parent();
+                    at = at.parent().resolve_mount();
                 }
                 path::SELF_NAME => {}
                 _ => break,
             }
         }
 
-        // TODO resolve link target
-
         if element.is_empty() && rest.is_empty() {
             return Ok(at);
         }
 
-        let node = at.lookup_or_load(element)?;
+        let mut node = at.lookup_or_load(element)?.resolve_mount();
+
+        // Links are always followed unless they're the last path element
+        if node.kind() == VnodeKind::Symlink && (follow || !rest.is_empty()) {
+            node = self.resolve_link(node, depth)?;
+        }
 
         if rest.is_empty() {
             Ok(node)
         } else {
-            self._find(node, rest, follow)
+            self._find(node, rest, follow, depth)
         }
     }
 
@@ -65,7 +123,47 @@ impl IoContext {
             self.cwd.clone()
         };
 
-        self._find(at, path, follow)
+        self._find(at, path, follow, 0)
+    }
+
+    // Resolves the directory containing the last element of the path
+    fn find_parent<'a>(
+        &self,
+        at: Option<VnodeRef>,
+        path: &'a str,
+    ) -> Result<(VnodeRef, &'a str), Error> {
+        let (parent, name) = path::split_right(path);
+        let parent = if parent.is_empty() && path.starts_with(path::SEPARATOR) {
+            path::SEPARATOR_STR
+        } else {
+            parent
+        };
+
+        // The root itself has no parent entry
+        let name = name.trim_end_matches(path::SEPARATOR);
+
+        Ok((self.find(at, parent, true)?, name))
+    }
+
+    pub fn create_directory(&self, at: Option<VnodeRef>, path: &str) -> Result<VnodeRef, Error> {
+        let (parent, name) = self.find_parent(at, path)?;
+        parent.create(name, VnodeKind::Directory)
+    }
+
+    pub fn remove(&self, at: Option<VnodeRef>, path: &str, directory: bool) -> Result<(), Error> {
+        let (parent, name) = self.find_parent(at, path)?;
+        parent.remove(name, directory)
+    }
+
+    pub fn rename(
+        &self,
+        at: Option<VnodeRef>,
+        old_path: &str,
+        new_path: &str,
+    ) -> Result<(), Error> {
+        let (old_parent, old_name) = self.find_parent(at.clone(), old_path)?;
+        let (new_parent, new_name) = self.find_parent(at, new_path)?;
+        old_parent.rename(old_name, &new_parent, new_name)
     }
 
     pub fn open(
@@ -75,19 +173,49 @@ impl IoContext {
         opts: OpenFlags,
     ) -> Result<FileRef, Error> {
         let node = match self.find(at.clone(), path, true) {
-            Err(Error::DoesNotExist) => todo!(),
-            o => o,
-        }?;
+            Ok(_) if opts.is_create() && opts.is_exclusive() => {
+                return Err(Error::AlreadyExists);
+            }
+            Ok(node) => node,
+            Err(Error::DoesNotExist) if opts.is_create() => {
+                let (parent, name) = self.find_parent(at, path)?;
+                parent.create(name, VnodeKind::Regular)?
+            }
+            Err(e) => return Err(e),
+        };
 
-        node.open(opts)
+        let file = node.open(opts)?;
+
+        if opts.is_truncate() && opts.is_write() && node.kind() == VnodeKind::Regular {
+            node.truncate(0)?;
+        }
+
+        Ok(file)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use abi::error::Error;
+    use abi::{
+        error::Error,
+        io::{DirectoryEntry, FileMode, FileType, OpenFlags, SeekFrom},
+    };
+    use alloc::{
+        boxed::Box,
+        string::{String, ToString},
+        sync::Arc,
+        vec::Vec,
+    };
+    use core::{
+        any::Any,
+        cell::Ref,
+        sync::atomic::{AtomicBool, Ordering},
+    };
 
-    use crate::{node::VnodeRef, IoContext};
+    use crate::{
+        node::VnodeRef, BlockDevice, CharDevice, CharDeviceWrapper, File, FileRef, Filesystem,
+        IoContext, Read, Vnode, VnodeImpl, VnodeKind, Write,
+    };
     use std::fmt;
 
     macro_rules! node {
@@ -106,6 +234,317 @@ mod tests {
         }};
     }
 
+    struct DummyFs {
+        root: VnodeRef,
+    }
+
+    impl Filesystem for DummyFs {
+        fn root(self: Arc<Self>) -> Result<VnodeRef, Error> {
+            Ok(self.root.clone())
+        }
+
+        fn dev(self: Arc<Self>) -> Option<&'static dyn BlockDevice> {
+            None
+        }
+
+        fn data(&self) -> Option<Ref<'_, dyn Any>> {
+            None
+        }
+    }
+
+    // Directory whose entries are only created once looked up
+    struct LazyDirectory {
+        names: &'static [&'static str],
+    }
+
+    impl VnodeImpl for LazyDirectory {
+        fn create(
+            &mut self,
+            _at: &VnodeRef,
+            _name: &str,
+            _kind: VnodeKind,
+        ) -> Result<VnodeRef, Error> {
+            Err(Error::NotImplemented)
+        }
+
+        fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn read(
+            &mut self,
+            _node: &VnodeRef,
+            _pos: usize,
+            _data: &mut [u8],
+        ) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn lookup(&mut self, _at: &VnodeRef, name: &str) -> Result<VnodeRef, Error> {
+            if self.names.contains(&name) {
+                Ok(Vnode::new(name, VnodeKind::Regular))
+            } else {
+                Err(Error::DoesNotExist)
+            }
+        }
+
+        fn read_dir(&mut self, _at: &VnodeRef, pos: usize) -> Result<Option<VnodeRef>, Error> {
+            Ok(self
+                .names
+                .get(pos)
+                .map(|&name| Vnode::new(name, VnodeKind::Regular)))
+        }
+    }
+
+    struct LinkNode {
+        target: &'static str,
+    }
+
+    impl VnodeImpl for LinkNode {
+        fn create(
+            &mut self,
+            _at: &VnodeRef,
+            _name: &str,
+            _kind: VnodeKind,
+        ) -> Result<VnodeRef, Error> {
+            Err(Error::NotADirectory)
+        }
+
+        fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+            Err(Error::InvalidFile)
+        }
+
+        fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn read(
+            &mut self,
+            _node: &VnodeRef,
+            _pos: usize,
+            _data: &mut [u8],
+        ) -> Result<usize, Error> {
+            Err(Error::InvalidFile)
+        }
+
+        fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+            Err(Error::InvalidFile)
+        }
+
+        fn read_link(&mut self, _node: &VnodeRef) -> Result<String, Error> {
+            Ok(self.target.to_string())
+        }
+    }
+
+    fn link(name: &'static str, target: &'static str) -> VnodeRef {
+        let node = Vnode::new(name, VnodeKind::Symlink);
+        node.set_data(Box::new(LinkNode { target }));
+        node
+    }
+
+    // Writable in-memory filesystem
+    struct MemoryDirectory;
+
+    struct MemoryFile {
+        data: Vec<u8>,
+    }
+
+    impl VnodeImpl for MemoryDirectory {
+        fn create(
+            &mut self,
+            _at: &VnodeRef,
+            name: &str,
+            kind: VnodeKind,
+        ) -> Result<VnodeRef, Error> {
+            let node = Vnode::new(name, kind);
+            match kind {
+                VnodeKind::Directory => node.set_data(Box::new(MemoryDirectory)),
+                VnodeKind::Regular => node.set_data(Box::new(MemoryFile { data: Vec::new() })),
+                _ => return Err(Error::NotSupported),
+            }
+            Ok(node)
+        }
+
+        fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn read(
+            &mut self,
+            _node: &VnodeRef,
+            _pos: usize,
+            _data: &mut [u8],
+        ) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn write(&mut self, _node: &VnodeRef, _pos: usize, _data: &[u8]) -> Result<usize, Error> {
+            Err(Error::IsADirectory)
+        }
+
+        fn remove(&mut self, _at: &VnodeRef, _name: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn rename(
+            &mut self,
+            _at: &VnodeRef,
+            _name: &str,
+            _new_parent: &VnodeRef,
+            _new_name: &str,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    impl VnodeImpl for MemoryFile {
+        fn create(
+            &mut self,
+            _at: &VnodeRef,
+            _name: &str,
+            _kind: VnodeKind,
+        ) -> Result<VnodeRef, Error> {
+            Err(Error::NotADirectory)
+        }
+
+        fn open(&mut self, _node: &VnodeRef, _opts: OpenFlags) -> Result<usize, Error> {
+            Ok(0)
+        }
+
+        fn close(&mut self, _node: &VnodeRef) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn read(&mut self, _node: &VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Error> {
+            if pos >= self.data.len() {
+                return Ok(0);
+            }
+            let count = data.len().min(self.data.len() - pos);
+            data[..count].copy_from_slice(&self.data[pos..pos + count]);
+            Ok(count)
+        }
+
+        fn write(&mut self, _node: &VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Error> {
+            if self.data.len() < pos + data.len() {
+                self.data.resize(pos + data.len(), 0);
+            }
+            self.data[pos..pos + data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn truncate(&mut self, _node: &VnodeRef, size: usize) -> Result<(), Error> {
+            self.data.resize(size, 0);
+            Ok(())
+        }
+
+        fn size(&mut self, _node: &VnodeRef) -> Result<usize, Error> {
+            Ok(self.data.len())
+        }
+    }
+
+    fn memory_root() -> VnodeRef {
+        let root = Vnode::new("", VnodeKind::Directory);
+        root.set_data(Box::new(MemoryDirectory));
+        root
+    }
+
+    // File which takes a while to report its size, leaving room for another append to slip in
+    struct SlowFile {
+        file: MemoryFile,
+    }
+
+    impl VnodeImpl for SlowFile {
+        fn create(
+            &mut self,
+            at: &VnodeRef,
+            name: &str,
+            kind: VnodeKind,
+        ) -> Result<VnodeRef, Error> {
+            self.file.create(at, name, kind)
+        }
+
+        fn open(&mut self, node: &VnodeRef, opts: OpenFlags) -> Result<usize, Error> {
+            self.file.open(node, opts)
+        }
+
+        fn close(&mut self, node: &VnodeRef) -> Result<(), Error> {
+            self.file.close(node)
+        }
+
+        fn read(&mut self, node: &VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Error> {
+            self.file.read(node, pos, data)
+        }
+
+        fn write(&mut self, node: &VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Error> {
+            self.file.write(node, pos, data)
+        }
+
+        fn size(&mut self, node: &VnodeRef) -> Result<usize, Error> {
+            std::thread::sleep(std::time::Duration::from_micros(10));
+            self.file.size(node)
+        }
+    }
+
+    fn read_all(ctx: &IoContext, path: &str) -> Vec<u8> {
+        let file = ctx.open(None, path, OpenFlags::new().read()).unwrap();
+        let mut buf = [0; 64];
+        let count = file.lock().read(&mut buf).unwrap();
+        buf[..count].to_vec()
+    }
+
+    struct NullDevice;
+
+    impl CharDevice for NullDevice {
+        fn read(&'static self, _blocking: bool, _data: &mut [u8]) -> Result<usize, Error> {
+            Ok(0)
+        }
+
+        fn write(&self, _blocking: bool, data: &[u8]) -> Result<usize, Error> {
+            Ok(data.len())
+        }
+    }
+
+    // Device which only has data to read when told so
+    struct InputDevice {
+        has_data: AtomicBool,
+    }
+
+    impl CharDevice for InputDevice {
+        fn read(&'static self, blocking: bool, data: &mut [u8]) -> Result<usize, Error> {
+            if !self.has_data.load(Ordering::Acquire) {
+                // There's nothing to actually wait for in tests
+                return if blocking {
+                    Ok(0)
+                } else {
+                    Err(Error::WouldBlock)
+                };
+            }
+
+            data.fill(b'x');
+            Ok(data.len())
+        }
+
+        fn write(&self, _blocking: bool, data: &[u8]) -> Result<usize, Error> {
+            Ok(data.len())
+        }
+
+        fn is_ready(&self, write: bool) -> Result<bool, Error> {
+            Ok(write || self.has_data.load(Ordering::Acquire))
+        }
+    }
+
     struct DumpNode<'a> {
         node: &'a VnodeRef,
     }
@@ -210,4 +649,864 @@ mod tests {
             ""
         );
     }
+
+    #[test]
+    fn test_mount() {
+        let t = node! {
+            "" [
+                node!("file1.txt"),
+                node! {
+                    "mnt" [
+                        node!("hidden.txt")
+                    ]
+                }
+            ]
+        };
+        let fs = Arc::new(DummyFs {
+            root: node! {
+                "" [
+                    node!("file2.txt"),
+                    node!("sub" [])
+                ]
+            },
+        });
+
+        let ctx = IoContext::new(t);
+        let mnt = ctx.find(None, "/mnt", false).unwrap();
+
+        mnt.mount(fs.clone()).unwrap();
+        assert_eq!(mnt.mount(fs.clone()).unwrap_err(), Error::AlreadyExists);
+
+        // Lookups descend into the mounted filesystem
+        assert_eq!(
+            ctx.find(None, "/mnt/file2.txt", false).unwrap().name(),
+            "file2.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/mnt/hidden.txt", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+        let sub = ctx.find(None, "/mnt/sub", false).unwrap();
+
+        // And climb back out of it
+        assert_eq!(
+            ctx.find(Some(sub.clone()), "../../file1.txt", false)
+                .unwrap()
+                .name(),
+            "file1.txt"
+        );
+        assert!(Arc::ptr_eq(
+            &ctx.find(Some(sub.clone()), "../../mnt/sub", false).unwrap(),
+            &sub
+        ));
+
+        // Only filesystem roots can be unmounted
+        assert_eq!(sub.unmount().unwrap_err(), Error::InvalidArgument);
+        ctx.find(None, "/mnt", false).unwrap().unmount().unwrap();
+
+        assert_eq!(
+            ctx.find(None, "/mnt/hidden.txt", false).unwrap().name(),
+            "hidden.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/mnt/file2.txt", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+    }
+
+    #[test]
+    fn test_vnode_load() {
+        let root = node!("" []);
+        root.set_data(Box::new(LazyDirectory {
+            names: &["file1.txt", "file2.txt"],
+        }));
+        let ctx = IoContext::new(root.clone());
+
+        // Entries are loaded into the cache on lookup
+        assert!(root.lookup("file1.txt").is_none());
+        let file1 = ctx.find(None, "/file1.txt", false).unwrap();
+        assert!(Arc::ptr_eq(&root.lookup("file1.txt").unwrap(), &file1));
+        assert!(Arc::ptr_eq(
+            &ctx.find(None, "file1.txt", false).unwrap(),
+            &file1
+        ));
+        assert_eq!(
+            ctx.find(None, "/file3.txt", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+        assert_eq!(
+            root.create("file2.txt", VnodeKind::Regular).unwrap_err(),
+            Error::AlreadyExists
+        );
+
+        // Directory listing reuses the cached nodes
+        assert!(Arc::ptr_eq(&root.read_dir(0).unwrap().unwrap(), &file1));
+        assert_eq!(root.read_dir(1).unwrap().unwrap().name(), "file2.txt");
+        assert!(root.read_dir(2).unwrap().is_none());
+
+        // Only the entries not referenced anywhere else are evicted
+        root.evict_unused();
+        assert!(root.lookup("file1.txt").is_some());
+        assert!(root.lookup("file2.txt").is_none());
+
+        drop(file1);
+        root.evict_unused();
+        assert!(root.lookup("file1.txt").is_none());
+
+        // Nodes which only exist in the cache are kept
+        let t = node!(""[node!("file1.txt")]);
+        t.evict_unused();
+        assert_eq!(t.read_dir(0).unwrap().unwrap().name(), "file1.txt");
+        assert!(t.read_dir(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_symlink_find() {
+        let t = node! {
+            "" [
+                node!("file1.txt"),
+                link("abs-file", "/dir1/file2.txt"),
+                link("abs-dir", "/dir1"),
+                link("loop1", "loop2"),
+                link("loop2", "./loop1"),
+                link("dangling", "file3.txt"),
+                node! {
+                    "dir1" [
+                        node!("file2.txt"),
+                        link("rel-file", "../file1.txt"),
+                        link("rel-link", "../abs-dir"),
+                        link("self", ".")
+                    ]
+                }
+            ]
+        };
+
+        let ctx = IoContext::new(t);
+
+        // Links are followed
+        assert_eq!(
+            ctx.find(None, "/abs-file", true).unwrap().name(),
+            "file2.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/dir1/rel-file", true).unwrap().name(),
+            "file1.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/dir1/rel-link", true).unwrap().name(),
+            "dir1"
+        );
+        assert_eq!(
+            ctx.find(None, "/abs-dir/self/self/rel-file", false)
+                .unwrap()
+                .name(),
+            "rel-file"
+        );
+        assert_eq!(
+            ctx.find(None, "/dir1/rel-link/file2.txt", false)
+                .unwrap()
+                .name(),
+            "file2.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/abs-dir/../file1.txt", true)
+                .unwrap()
+                .name(),
+            "file1.txt"
+        );
+
+        // The last element is not followed unless requested
+        assert_eq!(ctx.find(None, "/abs-dir", false).unwrap().name(), "abs-dir");
+        assert_eq!(
+            ctx.find(None, "/abs-dir", false)
+                .unwrap()
+                .read_link()
+                .unwrap(),
+            "/dir1"
+        );
+        assert_eq!(
+            ctx.find(None, "/dangling", false).unwrap().name(),
+            "dangling"
+        );
+
+        // Broken links
+        assert_eq!(
+            ctx.find(None, "/dangling", true).unwrap_err(),
+            Error::DoesNotExist
+        );
+        assert_eq!(
+            ctx.find(None, "/loop1", true).unwrap_err(),
+            Error::SymlinkLoop
+        );
+        assert_eq!(
+            ctx.find(None, "/loop1/file1.txt", false).unwrap_err(),
+            Error::SymlinkLoop
+        );
+        assert_eq!(
+            ctx.find(None, "/abs-file/file1.txt", true).unwrap_err(),
+            Error::NotADirectory
+        );
+        assert_eq!(
+            ctx.find(None, "/file1.txt", false)
+                .unwrap()
+                .read_link()
+                .unwrap_err(),
+            Error::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_read_dir() {
+        let t = node! {
+            "" [
+                node!("file1.txt"),
+                link("link", "file1.txt"),
+                node! {
+                    "dir1" [
+                        node!("file2.txt")
+                    ]
+                },
+                node!("lazy" [])
+            ]
+        };
+        let lazy = t.lookup("lazy").unwrap();
+        lazy.set_data(Box::new(LazyDirectory {
+            names: &["file3.txt", "file4.txt", "file5.txt"],
+        }));
+
+        let ctx = IoContext::new(t);
+        let mut entries = [DirectoryEntry {
+            id: 0,
+            ty: FileType::File,
+            name: [0; DirectoryEntry::NAME_MAX + 1],
+        }; 2];
+
+        // Entries are returned in batches until the directory is exhausted
+        let dir = ctx
+            .find(None, "/", false)
+            .unwrap()
+            .open_directory()
+            .unwrap();
+        let mut dir = dir.lock();
+
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 2);
+        assert_eq!(entries[0].name(), "file1.txt");
+        assert_eq!(entries[0].ty, FileType::File);
+        assert_eq!(
+            entries[0].id,
+            ctx.find(None, "/file1.txt", false).unwrap().id()
+        );
+        assert_eq!(entries[1].name(), "link");
+        assert_eq!(entries[1].ty, FileType::Symlink);
+
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 2);
+        assert_eq!(entries[0].name(), "dir1");
+        assert_eq!(entries[0].ty, FileType::Directory);
+        assert_eq!(entries[1].name(), "lazy");
+
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 0);
+        assert_eq!(dir.read(&mut [0; 4]).unwrap_err(), Error::IsADirectory);
+
+        // Entries of the filesystem are loaded while reading
+        let lazy = ctx.find(None, "/lazy", false).unwrap();
+        let dir = lazy.open_directory().unwrap();
+        let mut dir = dir.lock();
+
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 2);
+        assert_eq!(entries[0].name(), "file3.txt");
+        assert_eq!(entries[1].name(), "file4.txt");
+        assert_eq!(entries[1].id, lazy.lookup("file4.txt").unwrap().id());
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 1);
+        assert_eq!(entries[0].name(), "file5.txt");
+        assert_eq!(dir.read_dir(&mut entries).unwrap(), 0);
+
+        // Only directories can be read this way
+        assert!(matches!(
+            ctx.find(None, "/file1.txt", false)
+                .unwrap()
+                .open_directory(),
+            Err(Error::NotADirectory)
+        ));
+    }
+
+    #[test]
+    fn test_metadata() {
+        static NULL: NullDevice = NullDevice;
+
+        let t = node! {
+            "" [
+                node!("file1.txt"),
+                link("link", "file1.txt"),
+                node!("dev" [])
+            ]
+        };
+        let null = Vnode::new("null", VnodeKind::Char);
+        null.set_data(Box::new(CharDeviceWrapper::new(&NULL, 3)));
+        t.lookup("dev").unwrap().add_child(null.clone());
+
+        let ctx = IoContext::new(t);
+
+        // Defaults for nodes whose filesystem provides no metadata
+        let file = ctx.find(None, "/link", true).unwrap();
+        let metadata = file.metadata().unwrap();
+        assert_eq!(metadata.id, file.id());
+        assert_eq!(metadata.ty, FileType::File);
+        assert_eq!(metadata.mode, FileMode::default_file());
+        assert_eq!(metadata.size, 0);
+
+        let metadata = ctx.find(None, "/link", false).unwrap().metadata().unwrap();
+        assert_eq!(metadata.ty, FileType::Symlink);
+        assert_ne!(metadata.id, file.id());
+
+        let metadata = ctx.find(None, "/dev", false).unwrap().metadata().unwrap();
+        assert_eq!(metadata.ty, FileType::Directory);
+        assert_eq!(metadata.mode, FileMode::default_dir());
+
+        // Device nodes report the device they refer to
+        let metadata = ctx
+            .find(None, "/dev/null", false)
+            .unwrap()
+            .metadata()
+            .unwrap();
+        assert_eq!(metadata.id, null.id());
+        assert_eq!(metadata.ty, FileType::Char);
+        assert_eq!(metadata.device, 3);
+        assert!(metadata.mode.contains(FileMode::USER_READ));
+        assert!(!metadata.mode.contains(FileMode::OTHER_READ));
+
+        // Same information is available through an open file
+        let file = null.open(OpenFlags::new().read()).unwrap();
+        assert_eq!(file.lock().metadata().unwrap().device, 3);
+    }
+
+    #[test]
+    fn test_open_flags() {
+        let ctx = IoContext::new(memory_root());
+        let dir = ctx.find(None, "/", false).unwrap();
+        dir.create("dir1", VnodeKind::Directory).unwrap();
+
+        // Files are only created when requested
+        assert_eq!(
+            ctx.open(None, "/file1.txt", OpenFlags::new().write()).err(),
+            Some(Error::DoesNotExist)
+        );
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().write().create())
+            .unwrap();
+        file.lock().write(b"Hello, world").unwrap();
+        drop(file);
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"Hello, world");
+
+        let cwd = ctx.find(None, "/dir1", false).unwrap();
+        ctx.open(Some(cwd.clone()), "file2.txt", OpenFlags::new().create())
+            .unwrap();
+        ctx.open(Some(cwd), "../dir1/file3.txt", OpenFlags::new().create())
+            .unwrap();
+        assert_eq!(
+            ctx.find(None, "/dir1/file2.txt", false).unwrap().name(),
+            "file2.txt"
+        );
+        assert_eq!(
+            ctx.find(None, "/dir1/file3.txt", false).unwrap().name(),
+            "file3.txt"
+        );
+        assert_eq!(
+            ctx.open(None, "/dir2/file4.txt", OpenFlags::new().create())
+                .err(),
+            Some(Error::DoesNotExist)
+        );
+
+        // Existing files are opened as-is, unless creation is exclusive
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().write().create())
+            .unwrap();
+        file.lock().write(b"Hi").unwrap();
+        drop(file);
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"Hillo, world");
+        assert_eq!(
+            ctx.open(
+                None,
+                "/file1.txt",
+                OpenFlags::new().write().create().exclusive()
+            )
+            .err(),
+            Some(Error::AlreadyExists)
+        );
+        ctx.open(
+            None,
+            "/file5.txt",
+            OpenFlags::new().write().create().exclusive(),
+        )
+        .unwrap();
+
+        // Appending writes always go to the end of the file
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().write().append())
+            .unwrap();
+        file.lock().write(b"!").unwrap();
+        file.lock().seek(SeekFrom::Start(0)).unwrap();
+        file.lock().write(b"?").unwrap();
+        drop(file);
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"Hillo, world!?");
+
+        // Truncation only happens when writing
+        ctx.open(None, "/file1.txt", OpenFlags::new().read().truncate())
+            .unwrap();
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"Hillo, world!?");
+        ctx.open(None, "/file1.txt", OpenFlags::new().write().truncate())
+            .unwrap();
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"");
+    }
+
+    #[test]
+    fn test_seek() {
+        let ctx = IoContext::new(memory_root());
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().read().write().create())
+            .unwrap();
+        let mut file = file.lock();
+        let mut buf = [0; 4];
+
+        file.write(b"0123456789").unwrap();
+
+        assert_eq!(file.seek(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(file.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"2345");
+
+        assert_eq!(file.seek(SeekFrom::Current(-3)).unwrap(), 3);
+        assert_eq!(file.read(&mut buf[..1]).unwrap(), 1);
+        assert_eq!(buf[0], b'3');
+
+        assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert_eq!(file.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"89");
+
+        // Seeking past the end is allowed, the gap is filled on write
+        assert_eq!(file.seek(SeekFrom::End(2)).unwrap(), 12);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        file.write(b"ab").unwrap();
+        assert_eq!(file.seek(SeekFrom::Start(9)).unwrap(), 9);
+        assert_eq!(file.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"9\0\0a");
+
+        // Positions before the start of the file are invalid
+        assert_eq!(
+            file.seek(SeekFrom::Current(-100)).unwrap_err(),
+            Error::InvalidArgument
+        );
+        assert_eq!(
+            file.seek(SeekFrom::End(-15)).unwrap_err(),
+            Error::InvalidArgument
+        );
+        assert_eq!(file.seek(SeekFrom::Current(0)).unwrap(), 13);
+    }
+
+    #[test]
+    fn test_readiness() {
+        static INPUT: InputDevice = InputDevice {
+            has_data: AtomicBool::new(false),
+        };
+
+        let ctx = IoContext::new(memory_root());
+        let input = Vnode::new("input", VnodeKind::Char);
+        input.set_data(Box::new(CharDeviceWrapper::new(&INPUT, 4)));
+
+        // Regular files and directories never block
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().read().write().create())
+            .unwrap();
+        assert!(file.lock().is_ready(false).unwrap());
+        assert!(file.lock().is_ready(true).unwrap());
+        let dir = ctx
+            .find(None, "/", false)
+            .unwrap()
+            .open_directory()
+            .unwrap();
+        assert!(dir.lock().is_ready(false).unwrap());
+
+        // Devices report their own readiness
+        let file = input.open(OpenFlags::new().read().write()).unwrap();
+        assert!(!file.lock().is_ready(false).unwrap());
+        assert!(file.lock().is_ready(true).unwrap());
+        INPUT.has_data.store(true, Ordering::Release);
+        assert!(file.lock().is_ready(false).unwrap());
+
+        // Only the directions the file was opened for can be polled
+        let file = input.open(OpenFlags::new().read()).unwrap();
+        assert_eq!(file.lock().is_ready(true), Err(Error::BadDescriptor));
+    }
+
+    #[test]
+    fn test_shared_stream_io() {
+        // Device which checks that the file it's accessed through isn't kept locked
+        struct LockCheckDevice;
+
+        static FILE: std::sync::Mutex<Option<FileRef>> = std::sync::Mutex::new(None);
+        static DEVICE: LockCheckDevice = LockCheckDevice;
+
+        impl CharDevice for LockCheckDevice {
+            fn read(&'static self, _blocking: bool, data: &mut [u8]) -> Result<usize, Error> {
+                let file = FILE.lock().unwrap().clone().unwrap();
+                assert!(file.try_lock().is_some());
+                data.fill(b'x');
+                Ok(data.len())
+            }
+
+            fn write(&self, _blocking: bool, data: &[u8]) -> Result<usize, Error> {
+                let file = FILE.lock().unwrap().clone().unwrap();
+                assert!(file.try_lock().is_some());
+                Ok(data.len())
+            }
+        }
+
+        let device = Vnode::new("device", VnodeKind::Char);
+        device.set_data(Box::new(CharDeviceWrapper::new(&DEVICE, 5)));
+        let file = device.open(OpenFlags::new().read().write()).unwrap();
+        FILE.lock().unwrap().replace(file.clone());
+
+        let mut buf = [0; 4];
+        assert_eq!(File::read_shared(&file, &mut buf), Ok(4));
+        assert_eq!(&buf, b"xxxx");
+        assert_eq!(File::write_shared(&file, b"abc"), Ok(3));
+
+        // Regular files go through the locked path, which keeps track of the position
+        let ctx = IoContext::new(memory_root());
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().read().write().create())
+            .unwrap();
+        assert_eq!(File::write_shared(&file, b"abcd"), Ok(4));
+        file.lock().seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(File::read_shared(&file, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"bcd");
+
+        // Only the directions the file was opened for can be used
+        let file = device.open(OpenFlags::new().read()).unwrap();
+        assert_eq!(File::write_shared(&file, b"abc"), Err(Error::BadDescriptor));
+    }
+
+    #[test]
+    fn test_non_blocking() {
+        static INPUT: InputDevice = InputDevice {
+            has_data: AtomicBool::new(false),
+        };
+
+        let input = Vnode::new("input", VnodeKind::Char);
+        input.set_data(Box::new(CharDeviceWrapper::new(&INPUT, 4)));
+        let mut buf = [0; 4];
+
+        let file = input.open(OpenFlags::new().read().non_blocking()).unwrap();
+        let mut file = file.lock();
+        assert!(file.open_flags().is_non_blocking());
+        assert_eq!(file.read(&mut buf), Err(Error::WouldBlock));
+
+        // Mode can be switched after the file is opened
+        file.set_non_blocking(false);
+        assert!(!file.open_flags().is_non_blocking());
+        assert!(file.open_flags().is_read());
+        assert_eq!(file.read(&mut buf), Ok(0));
+
+        file.set_non_blocking(true);
+        INPUT.has_data.store(true, Ordering::Release);
+        assert_eq!(file.read(&mut buf), Ok(4));
+        assert_eq!(&buf, b"xxxx");
+
+        // Regular files are not affected
+        let ctx = IoContext::new(memory_root());
+        let file = ctx
+            .open(
+                None,
+                "/file1.txt",
+                OpenFlags::new().write().create().non_blocking(),
+            )
+            .unwrap();
+        assert_eq!(file.lock().write(b"abc"), Ok(3));
+        assert_eq!(read_all(&ctx, "/file1.txt"), b"abc");
+
+        // Directions the file wasn't opened for are rejected
+        assert_eq!(file.lock().read(&mut buf), Err(Error::BadDescriptor));
+    }
+
+    #[test]
+    fn test_remove() {
+        let ctx = IoContext::new(memory_root());
+
+        ctx.create_directory(None, "/dir1").unwrap();
+        ctx.create_directory(None, "dir1/dir2").unwrap();
+        ctx.open(None, "/dir1/file1.txt", OpenFlags::new().create())
+            .unwrap();
+        assert_eq!(
+            ctx.create_directory(None, "/dir1").unwrap_err(),
+            Error::AlreadyExists
+        );
+        assert_eq!(
+            ctx.create_directory(None, "/dir3/dir4").unwrap_err(),
+            Error::DoesNotExist
+        );
+
+        // Kinds must match
+        assert_eq!(
+            ctx.remove(None, "/dir1/file1.txt", true).unwrap_err(),
+            Error::NotADirectory
+        );
+        assert_eq!(
+            ctx.remove(None, "/dir1/dir2", false).unwrap_err(),
+            Error::IsADirectory
+        );
+
+        // Only empty directories can be removed
+        assert_eq!(
+            ctx.remove(None, "/dir1", true).unwrap_err(),
+            Error::NotEmpty
+        );
+
+        // Open files outlive their directory entries
+        let file = ctx
+            .open(None, "/dir1/file1.txt", OpenFlags::new().write())
+            .unwrap();
+        ctx.remove(None, "/dir1/file1.txt", false).unwrap();
+        assert_eq!(file.lock().write(b"data").unwrap(), 4);
+        assert_eq!(
+            ctx.find(None, "/dir1/file1.txt", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+
+        ctx.remove(None, "/dir1/dir2/", true).unwrap();
+        ctx.remove(None, "/dir1", true).unwrap();
+        assert_eq!(
+            ctx.find(None, "/dir1", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+        assert_eq!(
+            ctx.remove(None, "/dir1", true).unwrap_err(),
+            Error::DoesNotExist
+        );
+        assert_eq!(
+            ctx.remove(None, "/", true).unwrap_err(),
+            Error::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let ctx = IoContext::new(memory_root());
+
+        ctx.create_directory(None, "/dir1").unwrap();
+        ctx.create_directory(None, "/dir1/dir2").unwrap();
+        ctx.create_directory(None, "/mnt").unwrap();
+        let file = ctx
+            .open(None, "/file1.txt", OpenFlags::new().write().create())
+            .unwrap();
+        let node = ctx.find(None, "/file1.txt", false).unwrap();
+
+        // Rename within a directory
+        ctx.rename(None, "/file1.txt", "/file2.txt").unwrap();
+        assert_eq!(node.name(), "file2.txt");
+        assert!(Arc::ptr_eq(
+            &ctx.find(None, "/file2.txt", false).unwrap(),
+            &node
+        ));
+        assert_eq!(
+            ctx.find(None, "/file1.txt", false).unwrap_err(),
+            Error::DoesNotExist
+        );
+
+        // Move into another directory, keeping the open file usable
+        ctx.rename(None, "/file2.txt", "/dir1/dir2/file3.txt")
+            .unwrap();
+        assert!(Arc::ptr_eq(
+            &ctx.find(None, "/dir1/dir2/../dir2/file3.txt", false)
+                .unwrap(),
+            &node
+        ));
+        assert_eq!(file.lock().write(b"data").unwrap(), 4);
+
+        // Directories are moved with their contents
+        ctx.rename(None, "/dir1/dir2", "/dir3").unwrap();
+        assert!(Arc::ptr_eq(
+            &ctx.find(None, "/dir3/file3.txt", false).unwrap(),
+            &node
+        ));
+        assert_eq!(ctx.find(None, "/dir3/..", false).unwrap().name(), "");
+
+        // Invalid moves
+        assert_eq!(
+            ctx.rename(None, "/dir3", "/dir3/dir4").unwrap_err(),
+            Error::InvalidArgument
+        );
+        assert_eq!(
+            ctx.rename(None, "/dir1", "/dir3").unwrap_err(),
+            Error::AlreadyExists
+        );
+        assert_eq!(
+            ctx.rename(None, "/dir4", "/dir5").unwrap_err(),
+            Error::DoesNotExist
+        );
+
+        // Nodes can't cross filesystem boundaries
+        let fs = Arc::new(DummyFs {
+            root: memory_root(),
+        });
+        ctx.find(None, "/mnt", false).unwrap().mount(fs).unwrap();
+        assert_eq!(
+            ctx.rename(None, "/dir3/file3.txt", "/mnt/file3.txt")
+                .unwrap_err(),
+            Error::CrossDevice
+        );
+        assert_eq!(
+            ctx.rename(None, "/mnt", "/dir4").unwrap_err(),
+            Error::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_current_directory() {
+        let t = node! {
+            "" [
+                node!("file1.txt"),
+                node! {
+                    "dir1" [
+                        node!("dir2" [])
+                    ]
+                },
+                node!("mnt" [])
+            ]
+        };
+        let fs = Arc::new(DummyFs {
+            root: node! {
+                "" [
+                    node!("sub" [])
+                ]
+            },
+        });
+        let mut ctx = IoContext::new(t);
+
+        ctx.find(None, "/mnt", false).unwrap().mount(fs).unwrap();
+        assert_eq!(ctx.current_directory_path().unwrap(), "/");
+
+        // Relative paths are resolved from the current directory
+        ctx.set_current_directory(None, "/dir1").unwrap();
+        assert_eq!(ctx.current_directory_path().unwrap(), "/dir1");
+        assert_eq!(ctx.find(None, "dir2", false).unwrap().name(), "dir2");
+
+        ctx.set_current_directory(None, "dir2/..//dir2/").unwrap();
+        assert_eq!(ctx.current_directory_path().unwrap(), "/dir1/dir2");
+
+        // Paths are built across mounts
+        ctx.set_current_directory(None, "../../mnt/sub").unwrap();
+        assert_eq!(ctx.current_directory_path().unwrap(), "/mnt/sub");
+        ctx.set_current_directory(None, "..").unwrap();
+        assert_eq!(ctx.current_directory_path().unwrap(), "/mnt");
+        assert!(Arc::ptr_eq(
+            ctx.current_directory(),
+            &ctx.find(None, "/mnt", false).unwrap()
+        ));
+
+        assert_eq!(
+            ctx.set_current_directory(None, "/file1.txt").unwrap_err(),
+            Error::NotADirectory
+        );
+        assert_eq!(
+            ctx.set_current_directory(None, "/dir3").unwrap_err(),
+            Error::DoesNotExist
+        );
+        assert_eq!(ctx.current_directory_path().unwrap(), "/mnt");
+    }
+
+    #[test]
+    fn test_removed_current_directory() {
+        let mut ctx = IoContext::new(memory_root());
+
+        ctx.create_directory(None, "/dir1").unwrap();
+        ctx.set_current_directory(None, "/dir1").unwrap();
+        ctx.remove(None, "/dir1", true).unwrap();
+
+        // The directory is gone, so it no longer has a path
+        assert_eq!(
+            ctx.current_directory_path().unwrap_err(),
+            Error::DoesNotExist
+        );
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        const NAMES: &[&str] = &["file1.txt", "file2.txt", "file3.txt"];
+
+        let root = memory_root();
+        let lazy = Vnode::new("lazy", VnodeKind::Directory);
+        lazy.set_data(Box::new(LazyDirectory { names: NAMES }));
+        root.add_child(lazy);
+
+        let ctx = IoContext::new(root);
+        let threads = (0..4)
+            .map(|_| {
+                let ctx = ctx.clone();
+                std::thread::spawn(move || {
+                    let mut created = 0;
+                    let mut ids = Vec::new();
+
+                    for i in 0..32 {
+                        let path = std::format!("/dir{}", i);
+                        match ctx.create_directory(None, &path) {
+                            Ok(_) => created += 1,
+                            Err(Error::AlreadyExists) => (),
+                            Err(e) => panic!("{:?}", e),
+                        }
+                    }
+                    for name in NAMES {
+                        let path = std::format!("/lazy/{}", name);
+                        ids.push(ctx.find(None, &path, false).unwrap().id());
+                    }
+
+                    (created, ids)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let results = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // Each entry is only created or loaded once
+        assert_eq!(results.iter().map(|(count, _)| count).sum::<usize>(), 32);
+        assert!(results.iter().all(|(_, ids)| *ids == results[0].1));
+    }
+
+    #[test]
+    fn test_concurrent_append() {
+        let node = Vnode::new("log.txt", VnodeKind::Regular);
+        node.set_data(Box::new(SlowFile {
+            file: MemoryFile { data: Vec::new() },
+        }));
+
+        let threads = [b"aaaa", b"bbbb"]
+            .into_iter()
+            .map(|chunk| {
+                let file = node.open(OpenFlags::new().write().append()).unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..64 {
+                        assert_eq!(file.lock().write(chunk).unwrap(), 4);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // No append overwrote another one
+        let file = node.open(OpenFlags::new().read()).unwrap();
+        let mut data = [0; 2 * 64 * 4 + 1];
+        let mut count = 0;
+        while let Ok(n @ 1..) = file.lock().read(&mut data[count..]) {
+            count += n;
+        }
+
+        assert_eq!(count, 2 * 64 * 4);
+        assert!(data[..count]
+            .chunks(4)
+            .all(|chunk| chunk == b"aaaa" || chunk == b"bbbb"));
+        assert_eq!(data.chunks(4).filter(|&c| c == b"aaaa").count(), 64);
+    }
 }
@@ -13,12 +13,16 @@ pub(crate) mod file;
 pub(crate) mod fs;
 pub(crate) mod ioctx;
 pub(crate) mod node;
+pub(crate) mod stream;
+pub mod sync;
 
 pub use self::block::BlockDevice;
 pub use self::char::{CharDevice, CharDeviceWrapper};
 pub use file::{File, FileFlags, FileRef};
+pub use fs::Filesystem;
 pub use ioctx::IoContext;
 pub use node::{Vnode, VnodeImpl, VnodeKind, VnodeRef, VnodeWeak};
+pub use stream::{Stream, StreamRef};
 
 pub trait Write {
     fn write(&mut self, data: &[u8]) -> Result<usize, Error>;
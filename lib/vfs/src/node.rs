@@ -1,42 +1,72 @@
 use core::{
-    cell::{RefCell, RefMut},
     fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 
-use abi::{error::Error, io::OpenFlags};
+use abi::{
+    error::Error,
+    io::{DirectoryEntry, FileMetadata, FileMode, FileType, OpenFlags},
+    path,
+};
 use alloc::{
     boxed::Box,
-    rc::{Rc, Weak},
     string::String,
+    sync::{Arc, Weak},
     vec::Vec,
 };
+use lock_api::RawMutex;
 
-use crate::file::{File, FileFlags, FileRef};
+use crate::{
+    file::{File, FileFlags, FileRef},
+    fs::Filesystem,
+    stream::StreamRef,
+    sync::{Lock, LockGuard, RawLock},
+};
 
-pub type VnodeRef = Rc<Vnode>;
+pub type VnodeRef = Arc<Vnode>;
 pub type VnodeWeak = Weak<Vnode>;
 
+// Loading a child into a directory with this many cached entries drops the unused ones first
+const MAX_CACHED_CHILDREN: usize = 64;
+
+static RENAME_LOCK: Lock<()> = Lock::const_new(RawLock::INIT, ());
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VnodeKind {
     Directory,
     Regular,
     Char,
     Block,
+    Symlink,
+    Pipe,
+}
+
+pub(crate) struct Mount {
+    fs: Arc<dyn Filesystem>,
+    root: VnodeRef,
 }
 
 pub(crate) struct TreeNode {
     parent: Option<VnodeWeak>,
     children: Vec<VnodeRef>,
+    // Filesystem mounted over this directory
+    mount: Option<Mount>,
+    // Directory this node is mounted over, if it's a filesystem root
+    mountpoint: Option<VnodeWeak>,
+    // Node was loaded from the filesystem and can be dropped from the cache once unused
+    loaded: bool,
 }
 
 pub struct Vnode {
-    name: String,
-    tree: RefCell<TreeNode>,
+    id: u64,
+    name: Lock<String>,
+    tree: Lock<TreeNode>,
     kind: VnodeKind,
-    data: RefCell<Option<Box<dyn VnodeImpl>>>,
+    data: Lock<Option<Box<dyn VnodeImpl>>>,
 }
 
-pub trait VnodeImpl {
+pub trait VnodeImpl: Send {
     fn create(&mut self, at: &VnodeRef, name: &str, kind: VnodeKind) -> Result<VnodeRef, Error>;
 
     fn open(&mut self, node: &VnodeRef, opts: OpenFlags) -> Result<usize, Error>;
@@ -44,24 +74,91 @@ pub trait VnodeImpl {
 
     fn read(&mut self, node: &VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Error>;
     fn write(&mut self, node: &VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Error>;
+
+    // Nodes whose I/O can sleep are read from, written to and polled through this instead, so
+    // the node's data isn't kept locked while they block
+    fn stream(&mut self) -> Option<StreamRef> {
+        None
+    }
+
+    fn truncate(&mut self, _node: &VnodeRef, _size: usize) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn size(&mut self, _node: &VnodeRef) -> Result<usize, Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn lookup(&mut self, _at: &VnodeRef, _name: &str) -> Result<VnodeRef, Error> {
+        Err(Error::DoesNotExist)
+    }
+
+    fn read_dir(&mut self, _at: &VnodeRef, _pos: usize) -> Result<Option<VnodeRef>, Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn symlink(&mut self, _at: &VnodeRef, _name: &str, _target: &str) -> Result<VnodeRef, Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn read_link(&mut self, _node: &VnodeRef) -> Result<String, Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn metadata(&mut self, _node: &VnodeRef, _metadata: &mut FileMetadata) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn remove(&mut self, _at: &VnodeRef, _name: &str) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+
+    fn rename(
+        &mut self,
+        _at: &VnodeRef,
+        _name: &str,
+        _new_parent: &VnodeRef,
+        _new_name: &str,
+    ) -> Result<(), Error> {
+        Err(Error::NotImplemented)
+    }
+}
+
+impl VnodeKind {
+    // Streams have no position within them and can't be seeked
+    #[inline]
+    pub fn is_stream(self) -> bool {
+        matches!(self, Self::Char | Self::Pipe)
+    }
 }
 
 impl Vnode {
     pub fn new<S: Into<String>>(name: S, kind: VnodeKind) -> VnodeRef {
-        Rc::new(Self {
-            name: name.into(),
-            tree: RefCell::new(TreeNode {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        Arc::new(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: Lock::new(name.into()),
+            tree: Lock::new(TreeNode {
                 parent: None,
                 children: Vec::new(),
+                mount: None,
+                mountpoint: None,
+                loaded: false,
             }),
             kind,
-            data: RefCell::new(None),
+            data: Lock::new(None),
         })
     }
 
     #[inline]
-    pub fn name(&self) -> &str {
-        &self.name
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> String {
+        self.name.lock().clone()
     }
 
     #[inline]
@@ -70,19 +167,36 @@ impl Vnode {
     }
 
     #[inline]
-    pub fn data(&self) -> RefMut<Option<Box<dyn VnodeImpl>>> {
-        self.data.borrow_mut()
+    pub fn data(&self) -> LockGuard<'_, Option<Box<dyn VnodeImpl>>> {
+        self.data.lock()
     }
 
     pub fn parent(self: &VnodeRef) -> VnodeRef {
-        match &self.tree.borrow().parent {
+        let (parent, mountpoint) = {
+            let tree = self.tree.lock();
+            (tree.parent.clone(), tree.mountpoint.clone())
+        };
+
+        // Roots of mounted filesystems continue into the tree they're mounted in
+        if let Some(mountpoint) = mountpoint {
+            return mountpoint.upgrade().unwrap().parent();
+        }
+
+        match parent {
             Some(parent) => parent.upgrade().unwrap(),
             None => self.clone(),
         }
     }
 
+    fn stream(&self) -> Result<Option<StreamRef>, Error> {
+        match *self.data() {
+            Some(ref mut data) => Ok(data.stream()),
+            None => Err(Error::NotImplemented),
+        }
+    }
+
     pub fn set_data(&self, data: Box<dyn VnodeImpl>) {
-        self.data.borrow_mut().replace(data);
+        self.data.lock().replace(data);
     }
 
     #[inline]
@@ -92,16 +206,182 @@ impl Vnode {
 
     // Cache tree operations
     pub fn add_child(self: &VnodeRef, child: VnodeRef) {
-        let parent_weak = Rc::downgrade(self);
-        let mut parent_borrow = self.tree.borrow_mut();
+        let parent_weak = Arc::downgrade(self);
+
+        assert!(child.tree.lock().parent.replace(parent_weak).is_none());
+        self.tree.lock().children.push(child);
+    }
+
+    // Mount operations
+    pub fn mount(self: &VnodeRef, fs: Arc<dyn Filesystem>) -> Result<(), Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        let root = fs.clone().root()?;
+
+        if !root.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        // Tree locks are never held across nodes here: the root is claimed first and released
+        // again if the mountpoint turns out to be taken
+        {
+            let mut root_tree = root.tree.lock();
+            // The root is already attached somewhere else
+            if root_tree.parent.is_some() || root_tree.mountpoint.is_some() {
+                return Err(Error::AlreadyExists);
+            }
+            root_tree.mountpoint = Some(Arc::downgrade(self));
+        }
 
-        assert!(child
-            .tree
-            .borrow_mut()
-            .parent
-            .replace(parent_weak)
-            .is_none());
-        parent_borrow.children.push(child);
+        let mut tree = self.tree.lock();
+        if tree.mount.is_some() {
+            drop(tree);
+            root.tree.lock().mountpoint = None;
+            return Err(Error::AlreadyExists);
+        }
+        tree.mount = Some(Mount { fs, root });
+
+        Ok(())
+    }
+
+    pub fn unmount(self: &VnodeRef) -> Result<(), Error> {
+        let mountpoint = {
+            let mut tree = self.tree.lock();
+
+            if tree.mount.is_some() {
+                // Something else is mounted on top of this filesystem
+                return Err(Error::InvalidArgument);
+            }
+
+            tree.mountpoint
+                .take()
+                .ok_or(Error::InvalidArgument)?
+                .upgrade()
+                .unwrap()
+        };
+
+        mountpoint.tree.lock().mount = None;
+
+        Ok(())
+    }
+
+    pub fn mounted_filesystem(&self) -> Option<Arc<dyn Filesystem>> {
+        self.tree.lock().mount.as_ref().map(|m| m.fs.clone())
+    }
+
+    pub fn resolve_mount(self: &VnodeRef) -> VnodeRef {
+        let mut node = self.clone();
+
+        loop {
+            let root = node.tree.lock().mount.as_ref().map(|m| m.root.clone());
+
+            match root {
+                Some(root) => node = root,
+                None => break node,
+            }
+        }
+    }
+
+    // Builds the absolute path of the node, as seen from `root`, walking up through its parents
+    // and crossing into the trees filesystems are mounted in
+    pub fn path_from(self: &VnodeRef, root: &VnodeRef) -> Result<String, Error> {
+        let mut components = Vec::new();
+        let mut node = self.clone();
+
+        while !Arc::ptr_eq(&node, root) {
+            let (parent, mountpoint) = {
+                let tree = node.tree.lock();
+                (tree.parent.clone(), tree.mountpoint.clone())
+            };
+
+            if let Some(mountpoint) = mountpoint {
+                node = mountpoint.upgrade().unwrap();
+                continue;
+            }
+
+            // Reached the top without meeting the root: the node was either detached from the
+            // tree or is located outside of it
+            let parent = parent.ok_or(Error::DoesNotExist)?.upgrade().unwrap();
+
+            components.push(node.name());
+            node = parent;
+        }
+
+        if components.is_empty() {
+            return Ok(path::SEPARATOR_STR.into());
+        }
+
+        let mut result = String::new();
+        for component in components.iter().rev() {
+            result.push(path::SEPARATOR);
+            result.push_str(component);
+        }
+
+        Ok(result)
+    }
+
+    // Returns the root of the filesystem the node belongs to
+    fn fs_root(self: &VnodeRef) -> VnodeRef {
+        let mut node = self.clone();
+
+        loop {
+            let parent = node
+                .tree
+                .lock()
+                .parent
+                .as_ref()
+                .map(|p| p.upgrade().unwrap());
+
+            match parent {
+                Some(parent) => node = parent,
+                None => break node,
+            }
+        }
+    }
+
+    // Returns `true` if the node is `other` or is located somewhere below it
+    fn is_descendant_of(self: &VnodeRef, other: &VnodeRef) -> bool {
+        let mut node = self.clone();
+
+        loop {
+            if Arc::ptr_eq(&node, other) {
+                break true;
+            }
+
+            let parent = node
+                .tree
+                .lock()
+                .parent
+                .as_ref()
+                .map(|p| p.upgrade().unwrap());
+
+            match parent {
+                Some(parent) => node = parent,
+                None => break false,
+            }
+        }
+    }
+
+    // Checks if the node can be detached from its current place in the tree
+    fn check_detachable(self: &VnodeRef) -> Result<(), Error> {
+        let tree = self.tree.lock();
+
+        // Mountpoints and mounted filesystem roots can't be moved around
+        if tree.mount.is_some() || tree.mountpoint.is_some() || tree.parent.is_none() {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    fn remove_child(self: &VnodeRef, child: &VnodeRef) {
+        self.tree
+            .lock()
+            .children
+            .retain(|node| !Arc::ptr_eq(node, child));
+        child.tree.lock().parent = None;
     }
 
     pub fn dump(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
@@ -109,11 +389,11 @@ impl Vnode {
             f.write_str("  ")?;
         }
 
-        write!(f, "{:?}", self.name)?;
+        write!(f, "{:?}", self.name.lock())?;
 
         match self.kind {
             VnodeKind::Directory => {
-                let tree = self.tree.borrow();
+                let tree = self.tree.lock();
                 if tree.children.is_empty() {
                     f.write_str(" []")?;
                 } else {
@@ -137,25 +417,318 @@ impl Vnode {
     pub fn lookup(self: &VnodeRef, name: &str) -> Option<VnodeRef> {
         assert!(self.is_directory());
         self.tree
-            .borrow()
+            .lock()
             .children
             .iter()
-            .find(|e| e.name == name)
+            .find(|e| *e.name.lock() == name)
             .cloned()
     }
 
-    //
     pub fn lookup_or_load(self: &VnodeRef, name: &str) -> Result<VnodeRef, Error> {
         // Lookup in cache
         if let Some(node) = self.lookup(name) {
             return Ok(node);
         }
 
-        // TODO load from FS
-        Err(Error::DoesNotExist)
+        let mut data = self.data();
+        self.lookup_or_load_locked(&mut data, name)
+    }
+
+    // Directory's data lock is held by the caller, so no other thread can load or add entries
+    // with the same name in the meantime
+    fn lookup_or_load_locked(
+        self: &VnodeRef,
+        data: &mut Option<Box<dyn VnodeImpl>>,
+        name: &str,
+    ) -> Result<VnodeRef, Error> {
+        // Entry might've been loaded while waiting for the lock
+        if let Some(node) = self.lookup(name) {
+            return Ok(node);
+        }
+
+        let node = if let Some(data) = data.as_mut() {
+            data.lookup(self, name)?
+        } else {
+            return Err(Error::DoesNotExist);
+        };
+
+        self.add_loaded_child(node.clone());
+
+        Ok(node)
+    }
+
+    fn add_loaded_child(self: &VnodeRef, child: VnodeRef) {
+        if self.tree.lock().children.len() >= MAX_CACHED_CHILDREN {
+            self.evict_unused();
+        }
+
+        child.tree.lock().loaded = true;
+        self.add_child(child);
+    }
+
+    fn is_evictable(self: &VnodeRef) -> bool {
+        let tree = self.tree.lock();
+
+        // Only the parent's cache refers to the node
+        tree.loaded
+            && tree.children.is_empty()
+            && tree.mount.is_none()
+            && Arc::strong_count(self) == 1
+    }
+
+    pub fn evict_unused(self: &VnodeRef) {
+        self.tree.lock().children.retain(|child| {
+            child.evict_unused();
+            !child.is_evictable()
+        });
     }
 
     // Node operations
+    // Checks if a new entry with given name can be added to the directory
+    fn check_new_entry(
+        self: &VnodeRef,
+        data: &mut Option<Box<dyn VnodeImpl>>,
+        name: &str,
+    ) -> Result<(), Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        if name.is_empty()
+            || name.len() > DirectoryEntry::NAME_MAX
+            || name == path::SELF_NAME
+            || name == path::PARENT_NAME
+            || name.contains(path::SEPARATOR)
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        match self.lookup_or_load_locked(data, name) {
+            Ok(_) => Err(Error::AlreadyExists),
+            Err(Error::DoesNotExist) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn create(self: &VnodeRef, name: &str, kind: VnodeKind) -> Result<VnodeRef, Error> {
+        let mut data = self.data();
+        self.check_new_entry(&mut data, name)?;
+
+        let node = if let Some(ref mut data) = *data {
+            data.create(self, name, kind)?
+        } else {
+            return Err(Error::NotImplemented);
+        };
+
+        self.add_child(node.clone());
+
+        Ok(node)
+    }
+
+    pub fn remove(self: &VnodeRef, name: &str, directory: bool) -> Result<(), Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        if name.is_empty() || name == path::SELF_NAME || name == path::PARENT_NAME {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut data = self.data();
+        let node = self.lookup_or_load_locked(&mut data, name)?;
+
+        match (directory, node.is_directory()) {
+            (true, false) => return Err(Error::NotADirectory),
+            (false, true) => return Err(Error::IsADirectory),
+            _ => (),
+        }
+
+        node.check_detachable()?;
+
+        // Keeps entries from being added to the directory until it's removed
+        let mut node_data = node.data();
+
+        if directory && node.read_dir_locked(&mut node_data, 0)?.is_some() {
+            return Err(Error::NotEmpty);
+        }
+
+        if let Some(ref mut data) = *data {
+            data.remove(self, name)?;
+        } else {
+            return Err(Error::NotImplemented);
+        }
+
+        self.remove_child(&node);
+
+        Ok(())
+    }
+
+    pub fn rename(
+        self: &VnodeRef,
+        name: &str,
+        new_parent: &VnodeRef,
+        new_name: &str,
+    ) -> Result<(), Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        if name.is_empty() || name == path::SELF_NAME || name == path::PARENT_NAME {
+            return Err(Error::InvalidArgument);
+        }
+
+        if Arc::ptr_eq(self, new_parent) {
+            let mut data = self.data();
+            return self.rename_locked(&mut data, name, new_parent, None, new_name);
+        }
+
+        // Moves between directories can change which of the two is an ancestor of the other, so
+        // they're done one at a time. Other operations only lock a parent before its child, so
+        // the ancestor is locked first here as well.
+        let _guard = RENAME_LOCK.lock();
+        let (mut data, mut new_data) = if self.is_descendant_of(new_parent) {
+            let new_data = new_parent.data();
+            (self.data(), new_data)
+        } else {
+            let data = self.data();
+            (data, new_parent.data())
+        };
+
+        self.rename_locked(&mut data, name, new_parent, Some(&mut new_data), new_name)
+    }
+
+    fn rename_locked(
+        self: &VnodeRef,
+        data: &mut Option<Box<dyn VnodeImpl>>,
+        name: &str,
+        new_parent: &VnodeRef,
+        new_data: Option<&mut Option<Box<dyn VnodeImpl>>>,
+        new_name: &str,
+    ) -> Result<(), Error> {
+        let node = self.lookup_or_load_locked(data, name)?;
+
+        if Arc::ptr_eq(self, new_parent) && name == new_name {
+            return Ok(());
+        }
+
+        node.check_detachable()?;
+
+        match new_data {
+            Some(new_data) => new_parent.check_new_entry(new_data, new_name)?,
+            None => self.check_new_entry(data, new_name)?,
+        }
+
+        if !Arc::ptr_eq(&self.fs_root(), &new_parent.fs_root()) {
+            return Err(Error::CrossDevice);
+        }
+
+        // A directory can't be moved inside itself
+        if new_parent.is_descendant_of(&node) {
+            return Err(Error::InvalidArgument);
+        }
+
+        if let Some(data) = data.as_mut() {
+            data.rename(self, name, new_parent, new_name)?;
+        } else {
+            return Err(Error::NotImplemented);
+        }
+
+        self.remove_child(&node);
+        *node.name.lock() = new_name.into();
+        new_parent.add_child(node);
+
+        Ok(())
+    }
+
+    pub fn symlink(self: &VnodeRef, name: &str, target: &str) -> Result<VnodeRef, Error> {
+        let mut data = self.data();
+        self.check_new_entry(&mut data, name)?;
+
+        let node = if let Some(ref mut data) = *data {
+            data.symlink(self, name, target)?
+        } else {
+            return Err(Error::NotImplemented);
+        };
+
+        self.add_child(node.clone());
+
+        Ok(node)
+    }
+
+    pub fn read_link(self: &VnodeRef) -> Result<String, Error> {
+        if self.kind != VnodeKind::Symlink {
+            return Err(Error::InvalidArgument);
+        }
+
+        if let Some(ref mut data) = *self.data() {
+            data.read_link(self)
+        } else {
+            Err(Error::NotImplemented)
+        }
+    }
+
+    pub fn metadata(self: &VnodeRef) -> Result<FileMetadata, Error> {
+        let mut metadata = FileMetadata {
+            id: self.id,
+            size: 0,
+            ty: self.kind.into(),
+            mode: if self.is_directory() {
+                FileMode::default_dir()
+            } else {
+                FileMode::default_file()
+            },
+            uid: 0,
+            gid: 0,
+            device: 0,
+            ctime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            atime: Duration::ZERO,
+        };
+
+        // Filesystem fills in whatever it knows about the node
+        if let Some(ref mut data) = *self.data() {
+            data.metadata(self, &mut metadata)?;
+        }
+
+        Ok(metadata)
+    }
+
+    pub fn read_dir(self: &VnodeRef, pos: usize) -> Result<Option<VnodeRef>, Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        let mut data = self.data();
+        self.read_dir_locked(&mut data, pos)
+    }
+
+    fn read_dir_locked(
+        self: &VnodeRef,
+        data: &mut Option<Box<dyn VnodeImpl>>,
+        pos: usize,
+    ) -> Result<Option<VnodeRef>, Error> {
+        let entry = if let Some(data) = data.as_mut() {
+            data.read_dir(self, pos)
+        } else {
+            Err(Error::NotImplemented)
+        };
+
+        match entry {
+            Ok(Some(node)) => {
+                if let Some(cached) = self.lookup(&node.name()) {
+                    return Ok(Some(cached));
+                }
+
+                self.add_loaded_child(node.clone());
+                Ok(Some(node))
+            }
+            Ok(None) => Ok(None),
+            // Contents of the directory only exist in the cache
+            Err(Error::NotImplemented) => Ok(self.tree.lock().children.get(pos).cloned()),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn open(self: &VnodeRef, flags: OpenFlags) -> Result<FileRef, Error> {
         let mut open_flags = FileFlags::empty();
 
@@ -165,6 +738,12 @@ impl Vnode {
         if flags.is_write() {
             open_flags |= FileFlags::WRITE;
         }
+        if flags.is_append() {
+            open_flags |= FileFlags::APPEND;
+        }
+        if flags.is_non_blocking() {
+            open_flags |= FileFlags::NONBLOCK;
+        }
 
         if self.kind == VnodeKind::Directory {
             return Err(Error::IsADirectory);
@@ -174,39 +753,138 @@ impl Vnode {
             let pos = data.open(self, flags)?;
             Ok(File::normal(self.clone(), pos, open_flags))
         } else {
-            todo!()
+            Err(Error::NotImplemented)
         }
     }
 
+    pub fn open_directory(self: &VnodeRef) -> Result<FileRef, Error> {
+        if !self.is_directory() {
+            return Err(Error::NotADirectory);
+        }
+
+        Ok(File::directory(self.clone()))
+    }
+
     pub fn close(self: &VnodeRef) -> Result<(), Error> {
         if let Some(ref mut data) = *self.data() {
             data.close(self)
         } else {
-            todo!()
+            Err(Error::NotImplemented)
         }
     }
 
     pub fn write(self: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Error> {
+        self.write_inner(pos, buf, true)
+    }
+
+    pub fn write_nonblocking(self: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Error> {
+        self.write_inner(pos, buf, false)
+    }
+
+    fn write_inner(
+        self: &VnodeRef,
+        pos: usize,
+        buf: &[u8],
+        blocking: bool,
+    ) -> Result<usize, Error> {
         if self.kind == VnodeKind::Directory {
-            todo!();
+            return Err(Error::IsADirectory);
+        }
+
+        if let Some(stream) = self.stream()? {
+            return stream.write(blocking, buf);
         }
 
         if let Some(ref mut data) = *self.data() {
             data.write(self, pos, buf)
         } else {
-            todo!()
+            Err(Error::NotImplemented)
+        }
+    }
+
+    // Writes the data at the end of the file, the size is looked up under the same lock so
+    // concurrent appends can't overwrite each other. Returns the position written at along with
+    // the count.
+    pub fn append(self: &VnodeRef, buf: &[u8]) -> Result<(usize, usize), Error> {
+        if self.kind == VnodeKind::Directory {
+            return Err(Error::IsADirectory);
+        }
+
+        if let Some(ref mut data) = *self.data() {
+            let pos = data.size(self)?;
+            let count = data.write(self, pos, buf)?;
+            Ok((pos, count))
+        } else {
+            Err(Error::NotImplemented)
+        }
+    }
+
+    pub fn truncate(self: &VnodeRef, size: usize) -> Result<(), Error> {
+        if self.kind == VnodeKind::Directory {
+            return Err(Error::IsADirectory);
+        }
+
+        if let Some(ref mut data) = *self.data() {
+            data.truncate(self, size)
+        } else {
+            Err(Error::NotImplemented)
+        }
+    }
+
+    pub fn size(self: &VnodeRef) -> Result<usize, Error> {
+        if let Some(ref mut data) = *self.data() {
+            data.size(self)
+        } else {
+            Err(Error::NotImplemented)
+        }
+    }
+
+    pub fn is_ready(self: &VnodeRef, write: bool) -> Result<bool, Error> {
+        match self.stream()? {
+            Some(stream) => stream.is_ready(write),
+            None => Ok(true),
         }
     }
 
     pub fn read(self: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_inner(pos, buf, true)
+    }
+
+    pub fn read_nonblocking(self: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_inner(pos, buf, false)
+    }
+
+    fn read_inner(
+        self: &VnodeRef,
+        pos: usize,
+        buf: &mut [u8],
+        blocking: bool,
+    ) -> Result<usize, Error> {
         if self.kind == VnodeKind::Directory {
-            todo!();
+            return Err(Error::IsADirectory);
+        }
+
+        if let Some(stream) = self.stream()? {
+            return stream.read(blocking, buf);
         }
 
         if let Some(ref mut data) = *self.data() {
             data.read(self, pos, buf)
         } else {
-            todo!()
+            Err(Error::NotImplemented)
+        }
+    }
+}
+
+impl From<VnodeKind> for FileType {
+    fn from(kind: VnodeKind) -> Self {
+        match kind {
+            VnodeKind::Directory => Self::Directory,
+            VnodeKind::Regular => Self::File,
+            VnodeKind::Char => Self::Char,
+            VnodeKind::Block => Self::Block,
+            VnodeKind::Symlink => Self::Symlink,
+            VnodeKind::Pipe => Self::Pipe,
         }
     }
 }
@@ -218,8 +896,10 @@ impl fmt::Debug for Vnode {
             VnodeKind::Regular => "REG ",
             VnodeKind::Char => "CHR ",
             VnodeKind::Block => "BLK ",
+            VnodeKind::Symlink => "LNK ",
+            VnodeKind::Pipe => "PIPE",
         };
 
-        write!(f, "[{} {}]", prefix, self.name)
+        write!(f, "[{} {}]", prefix, self.name.lock())
     }
 }
@@ -0,0 +1,17 @@
+use abi::error::Error;
+use alloc::sync::Arc;
+
+// Backend of a node whose I/O can sleep, like a device or a pipe end. It's used through a shared
+// reference without holding any of the node's locks, so implementations do their own locking.
+pub trait Stream: Send + Sync {
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Error>;
+    fn write(&self, blocking: bool, data: &[u8]) -> Result<usize, Error>;
+
+    // Streams which can block are expected to subscribe the caller to their readiness
+    // notifications when they report they're not ready
+    fn is_ready(&self, _write: bool) -> Result<bool, Error> {
+        Ok(true)
+    }
+}
+
+pub type StreamRef = Arc<dyn Stream>;
@@ -0,0 +1,58 @@
+//! Locks protecting the structures shared between threads. They're plain spinlocks by default.
+//! With the `external-lock` feature, the raw lock is supplied by the user of the crate instead,
+//! e.g. a kernel can provide one which also masks interrupts, by defining the functions declared
+//! below.
+
+pub type Lock<T> = lock_api::Mutex<RawLock, T>;
+pub type LockGuard<'a, T> = lock_api::MutexGuard<'a, RawLock, T>;
+
+#[cfg(not(feature = "external-lock"))]
+pub use spinning_top::RawSpinlock as RawLock;
+
+#[cfg(feature = "external-lock")]
+pub use self::external::RawLock;
+
+#[cfg(feature = "external-lock")]
+mod external {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use lock_api::{GuardNoSend, RawMutex};
+
+    extern "Rust" {
+        // Spins until the lock is taken. The user is free to keep its own data in the state, as
+        // long as it's non-zero while the lock is held.
+        fn __vfs_raw_lock(state: &AtomicUsize);
+        fn __vfs_raw_try_lock(state: &AtomicUsize) -> bool;
+        fn __vfs_raw_unlock(state: &AtomicUsize);
+    }
+
+    pub struct RawLock {
+        state: AtomicUsize,
+    }
+
+    unsafe impl RawMutex for RawLock {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = Self {
+            state: AtomicUsize::new(0),
+        };
+
+        // The lock may carry state of the CPU it was taken on
+        type GuardMarker = GuardNoSend;
+
+        fn lock(&self) {
+            unsafe { __vfs_raw_lock(&self.state) }
+        }
+
+        fn try_lock(&self) -> bool {
+            unsafe { __vfs_raw_try_lock(&self.state) }
+        }
+
+        unsafe fn unlock(&self) {
+            __vfs_raw_unlock(&self.state)
+        }
+
+        fn is_locked(&self) -> bool {
+            self.state.load(Ordering::Relaxed) != 0
+        }
+    }
+}
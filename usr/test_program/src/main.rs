@@ -7,7 +7,7 @@ fn main() {
     let mut f = OpenOptions::new()
         .read(true)
         .write(true)
-        .open("/ttyS0")
+        .open("/dev/ttyS0")
         .unwrap();
 
     let mut buf = [0; 1];